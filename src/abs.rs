@@ -0,0 +1,99 @@
+use core::ops::{Add, Sub};
+use core::time::Duration;
+use std::time::SystemTime;
+
+use crate::AnchoredTSC;
+
+/// A raw TSC reading tied to the `AnchoredTSC` that places it on the wall clock, for timeline
+/// arithmetic: `AbsTsc - AbsTsc` gives a `Duration` and `AbsTsc + Duration` a later `AbsTsc`.
+/// Readings only mix with readings of the same anchor (or a copy of it); subtracting across
+/// anchors is a bug caught by a debug assertion, and `checked_duration_since()` reports it as
+/// `None`
+#[derive(Debug, Clone, Copy)]
+pub struct AbsTsc<'a> {
+    raw: u64,
+    anchor: &'a AnchoredTSC,
+}
+
+impl AnchoredTSC {
+    /// The current reading as an `AbsTsc` on this anchor's timeline
+    pub fn now_abs(&self) -> AbsTsc<'_> {
+        self.abs(self.get_tsc().read())
+    }
+
+    /// The raw TSC value `raw` as an `AbsTsc` on this anchor's timeline
+    pub fn abs(&self, raw: u64) -> AbsTsc<'_> {
+        AbsTsc { raw, anchor: self }
+    }
+}
+
+impl AbsTsc<'_> {
+    pub fn raw(&self) -> u64 {
+        self.raw
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        self.anchor.tsc_to_system_time(self.raw)
+    }
+
+    // copies of an anchor are the same timeline, so compare by value rather than address
+    fn same_anchor(&self, other: &AbsTsc<'_>) -> bool {
+        self.anchor.get_anchor() == other.anchor.get_anchor()
+            && self.anchor.get_tsc() == other.anchor.get_tsc()
+    }
+
+    /// The time from `earlier` to `self`, saturating to zero if `earlier` is later like
+    /// `Instant`; `None` if the two readings have different anchors
+    pub fn checked_duration_since(&self, earlier: AbsTsc<'_>) -> Option<Duration> {
+        let tsc = self.anchor.get_tsc();
+        self.same_anchor(&earlier)
+            .then(|| tsc.tsc_to_duration(tsc.cycles_between(earlier.raw, self.raw).unwrap_or(0)))
+    }
+}
+
+impl Sub for AbsTsc<'_> {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Duration {
+        debug_assert!(
+            self.same_anchor(&rhs),
+            "subtracting AbsTsc readings of different anchors"
+        );
+        let tsc = self.anchor.get_tsc();
+        tsc.tsc_to_duration(tsc.cycles_between(rhs.raw, self.raw).unwrap_or(0))
+    }
+}
+
+// saturates at `u64::MAX` ticks rather than wrapping
+impl Add<Duration> for AbsTsc<'_> {
+    type Output = Self;
+
+    fn add(self, rhs: Duration) -> Self {
+        let ticks = self.anchor.get_tsc().duration_to_ticks(rhs);
+        Self {
+            raw: self.raw.saturating_add(ticks),
+            ..self
+        }
+    }
+}
+
+#[test]
+fn test_abs_tsc() {
+    let anchor = AnchoredTSC::new(crate::TSC::from_freq(1_000_000_000));
+    let copy = anchor;
+    let t0 = anchor.abs(5_000);
+    let t1 = t0 + Duration::from_micros(3);
+    assert_eq!(t1.raw(), 8_000);
+    assert_eq!(t1 - t0, Duration::from_micros(3));
+    assert_eq!(t0 - t1, Duration::ZERO);
+    assert_eq!(
+        copy.abs(8_000).checked_duration_since(t0),
+        Some(Duration::from_micros(3))
+    );
+    let wall = t1.to_system_time().duration_since(t0.to_system_time());
+    assert_eq!(wall.unwrap(), Duration::from_micros(3));
+
+    let other = AnchoredTSC::new(crate::TSC::from_freq(2_000_000_000));
+    assert_eq!(other.abs(8_000).checked_duration_since(t0), None);
+    assert!(anchor.now_abs().raw() >= t0.raw());
+}
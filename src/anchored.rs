@@ -0,0 +1,263 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::TSC;
+
+/// A `TSC` paired with a wall-clock anchor, used to produce cheap absolute (UNIX epoch)
+/// timestamps. The anchor is captured once, so NTP adjustments (or any other changes to the
+/// system clock) made after anchoring are not tracked. An `Instant` is anchored alongside, for
+/// handing TSC readings to code that only takes `Instant`s
+#[derive(Debug, Clone, Copy)]
+pub struct AnchoredTSC {
+    tsc: TSC,
+    anchor_tsc: u64,
+    anchor_time: SystemTime,
+    anchor_unix_nanos: u128,
+    anchor_instant_tsc: u64,
+    anchor_instant: Instant,
+}
+
+impl AnchoredTSC {
+    const ANCHOR_ATTEMPTS: usize = 8;
+
+    /// TAI - UTC, in effect since the leap second of 2016-12-31 (no further ones are announced)
+    pub const TAI_UTC_OFFSET_SECS: u64 = 37;
+
+    pub fn new(tsc: TSC) -> Self {
+        let (anchor_tsc, anchor_time) = Self::anchor(&tsc, SystemTime::now);
+        let (anchor_instant_tsc, anchor_instant) = Self::anchor(&tsc, Instant::now);
+
+        Self {
+            tsc,
+            anchor_tsc,
+            anchor_time,
+            anchor_unix_nanos: anchor_time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            anchor_instant_tsc,
+            anchor_instant,
+        }
+    }
+
+    // bracket `now()` between two reads of `tsc` and keep the attempt with the tightest
+    // bracket, taking its midpoint as the TSC value matching the returned time
+    fn anchor<T>(tsc: &TSC, now: impl Fn() -> T) -> (u64, T) {
+        let mut best = (u64::MAX, 0, now());
+        for _ in 0..Self::ANCHOR_ATTEMPTS {
+            let c0 = tsc.read();
+            let time = now();
+            let c1 = tsc.read();
+            let gap = tsc.cycles_between(c0, c1).unwrap_or(u64::MAX);
+            if gap < best.0 {
+                best = (gap, c0.wrapping_add(gap / 2) & tsc.counter_mask(), time);
+            }
+        }
+        (best.1, best.2)
+    }
+
+    // the signed distance from `anchor` to `raw`: `Ok` if `raw` is at or after it, wrapping
+    // like the counter does (see `TSC::cycles_between()`)
+    fn offset(&self, anchor: u64, raw: u64) -> Result<u64, u64> {
+        self.tsc
+            .cycles_between(anchor, raw)
+            .ok_or(anchor.wrapping_sub(raw) & self.tsc.counter_mask())
+    }
+
+    pub fn get_tsc(&self) -> &TSC {
+        &self.tsc
+    }
+
+    pub fn get_anchor(&self) -> (u64, SystemTime) {
+        (self.anchor_tsc, self.anchor_time)
+    }
+
+    pub fn get_instant_anchor(&self) -> (u64, Instant) {
+        (self.anchor_instant_tsc, self.anchor_instant)
+    }
+
+    /// The `Instant` matching the raw TSC value `raw`, found by offsetting the anchored
+    /// `Instant` (they can't be constructed any other way). Readings too far before the anchor
+    /// for `Instant` to represent clamp to the anchor
+    pub fn tsc_to_instant(&self, raw: u64) -> Instant {
+        match self.offset(self.anchor_instant_tsc, raw) {
+            Ok(after) => self.anchor_instant + self.tsc.tsc_to_duration(after),
+            Err(before) => self
+                .anchor_instant
+                .checked_sub(self.tsc.tsc_to_duration(before))
+                .unwrap_or(self.anchor_instant),
+        }
+    }
+
+    /// The wall-clock time matching the raw TSC value `raw`, by offsetting the anchored
+    /// `SystemTime`. Readings too far before the anchor to represent clamp to the anchor
+    pub fn tsc_to_system_time(&self, raw: u64) -> SystemTime {
+        match self.offset(self.anchor_tsc, raw) {
+            Ok(after) => self.anchor_time + self.tsc.tsc_to_duration(after),
+            Err(before) => self
+                .anchor_time
+                .checked_sub(self.tsc.tsc_to_duration(before))
+                .unwrap_or(self.anchor_time),
+        }
+    }
+
+    /// The raw TSC value the counter had (or will have) at wall-clock time `t`, for placing
+    /// externally produced timestamps on the TSC timeline. Saturates at 0 for times before the
+    /// counter started, and at `u64::MAX`
+    pub fn system_time_to_tsc(&self, t: SystemTime) -> u64 {
+        match t.duration_since(self.anchor_time) {
+            Ok(after) => self
+                .anchor_tsc
+                .saturating_add(self.tsc.duration_to_ticks(after)),
+            Err(before) => self
+                .anchor_tsc
+                .saturating_sub(self.tsc.duration_to_ticks(before.duration())),
+        }
+    }
+
+    pub fn now_unix_nanos(&self) -> u128 {
+        let delta = self.offset(self.anchor_tsc, self.tsc.read()).unwrap_or(0);
+        self.anchor_unix_nanos + self.tsc.tsc_to_ns(delta) as u128
+    }
+
+    /// Nanoseconds since the UNIX epoch on the TAI timescale: the anchor converted from UTC with
+    /// `TAI_UTC_OFFSET_SECS`, plus the TSC time elapsed since. Nothing after the anchor (leap
+    /// seconds, NTP steps or slewing) is applied, so unlike UTC this never jumps or repeats; it
+    /// is only as accurate as the anchor and the calibrated frequency
+    pub fn now_tai_nanos(&self) -> u128 {
+        self.now_unix_nanos() + Self::TAI_UTC_OFFSET_SECS as u128 * 1_000_000_000
+    }
+
+    /// The current time as a `chrono` UTC timestamp, with full nanosecond resolution
+    #[cfg(feature = "chrono")]
+    pub fn now_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_nanos(self.now_unix_nanos() as i64)
+    }
+
+    /// The current time as a `time` UTC timestamp, with full nanosecond resolution
+    #[cfg(feature = "time")]
+    pub fn now_offset_datetime(&self) -> time::OffsetDateTime {
+        time::OffsetDateTime::from_unix_timestamp_nanos(self.now_unix_nanos() as i128)
+            .expect("TSC timestamp out of OffsetDateTime range")
+    }
+}
+
+#[test]
+fn test_now_unix_nanos() {
+    let t = AnchoredTSC::new(TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap());
+    let n0 = t.now_unix_nanos();
+    assert!(n0 >= t.anchor_unix_nanos);
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let sys = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let n1 = t.now_unix_nanos();
+    assert!(n1 > n0);
+    assert!(n1.abs_diff(sys) < 5_000_000, "tsc={n1} sys={sys}");
+}
+
+#[test]
+fn test_tsc_to_instant() {
+    let t = AnchoredTSC::new(TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap());
+    let (anchor_tsc, anchor) = t.get_instant_anchor();
+    assert_eq!(t.tsc_to_instant(anchor_tsc), anchor);
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let raw = t.get_tsc().start();
+    let now = Instant::now();
+    let converted = t.tsc_to_instant(raw);
+    let diff = now
+        .checked_duration_since(converted)
+        .unwrap_or_else(|| converted - now);
+    assert!(diff.as_millis() < 5, "diff={diff:?}");
+
+    let freq = t.get_tsc().get_freq();
+    assert_eq!(
+        anchor - t.tsc_to_instant(anchor_tsc - freq / 1000),
+        t.get_tsc().tsc_to_duration(freq / 1000)
+    );
+}
+
+#[test]
+fn test_system_time_conversions() {
+    let t = AnchoredTSC::new(TSC::from_freq(1_000_000_000));
+    let (anchor_tsc, anchor) = t.get_anchor();
+    assert_eq!(t.tsc_to_system_time(anchor_tsc), anchor);
+    assert_eq!(t.system_time_to_tsc(anchor), anchor_tsc);
+
+    let second = std::time::Duration::from_secs(1);
+    assert_eq!(
+        t.tsc_to_system_time(anchor_tsc + 1_000_000_000),
+        anchor + second
+    );
+    assert_eq!(
+        t.system_time_to_tsc(anchor + second),
+        anchor_tsc + 1_000_000_000
+    );
+    if anchor_tsc > 1_000_000_000 {
+        assert_eq!(
+            t.tsc_to_system_time(anchor_tsc - 1_000_000_000),
+            anchor - second
+        );
+        assert_eq!(
+            t.system_time_to_tsc(anchor - second),
+            anchor_tsc - 1_000_000_000
+        );
+    }
+    assert_eq!(t.system_time_to_tsc(UNIX_EPOCH), 0);
+
+    let raw = anchor_tsc + 123_456_789;
+    assert_eq!(t.system_time_to_tsc(t.tsc_to_system_time(raw)), raw);
+}
+
+#[test]
+fn test_anchored_narrow_counter() {
+    let narrow = TSC {
+        counter_bits: 16,
+        ..TSC::from_freq(1_000_000_000)
+    };
+    let live = AnchoredTSC::new(narrow);
+    assert!(live.get_anchor().0 <= 0xffff);
+    assert!(live.now_unix_nanos() >= live.anchor_unix_nanos);
+
+    // anchored just before the counter wraps
+    let t = AnchoredTSC {
+        anchor_tsc: 0xfff0,
+        ..live
+    };
+    let (_, anchor) = t.get_anchor();
+    let ns = std::time::Duration::from_nanos;
+    assert_eq!(t.tsc_to_system_time(0x10), anchor + ns(0x20));
+    assert_eq!(t.tsc_to_system_time(0xffe0), anchor - ns(0x10));
+}
+
+#[test]
+fn test_now_tai_nanos() {
+    let t = AnchoredTSC::new(TSC::from_freq(3_000_000_000));
+    let utc = t.now_unix_nanos();
+    let tai = t.now_tai_nanos();
+    assert!(tai - utc >= 37_000_000_000);
+    assert!(tai - utc < 38_000_000_000);
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+#[test]
+fn test_now_datetime() {
+    let t = AnchoredTSC::new(TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap());
+    let sys = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as i128;
+
+    #[cfg(feature = "chrono")]
+    {
+        let dt = t.now_datetime().timestamp_nanos_opt().unwrap() as i128;
+        assert!((dt - sys).abs() < 5_000_000);
+    }
+    #[cfg(feature = "time")]
+    {
+        let dt = t.now_offset_datetime().unix_timestamp_nanos();
+        assert!((dt - sys).abs() < 5_000_000);
+    }
+}
@@ -0,0 +1,58 @@
+use core::future::Future;
+
+use crate::TSC;
+
+impl TSC {
+    /// Awaits `f` between two `read_tscp()` reads, returning its output and the elapsed cycles.
+    /// An executor may resume the task on another core after any `.await`, where the counter can
+    /// be offset from the first core's, so the cycles are `None` if the reads' core ids differ
+    /// (or the counter went backwards). Without `rdtscp`, and on non-x86 targets, there's no core
+    /// id to compare; their counters are system-wide anyway
+    pub async fn measure_async<F: Future>(&self, f: F) -> (F::Output, Option<u64>) {
+        let (start, start_core) = Self::read_tscp();
+        let out = f.await;
+        let (end, end_core) = Self::read_tscp();
+        let cycles = if start_core == end_core {
+            end.checked_sub(start)
+        } else {
+            None
+        };
+        (out, cycles)
+    }
+}
+
+#[test]
+fn test_measure_async() {
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    // polls `f` to completion, returning how many polls it took
+    fn block_on<F: Future>(f: F) -> (F::Output, usize) {
+        let mut f = pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut polls = 1;
+        loop {
+            if let Poll::Ready(out) = f.as_mut().poll(&mut cx) {
+                return (out, polls);
+            }
+            polls += 1;
+        }
+    }
+
+    let t = TSC::from_freq(1_000_000_000);
+    let ((out, cycles), polls) = block_on(t.measure_async(core::future::ready(42)));
+    assert_eq!((out, polls), (42, 1));
+    assert!(cycles.is_some());
+
+    let mut pending = true;
+    let yield_once = core::future::poll_fn(|_| {
+        if core::mem::take(&mut pending) {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    });
+    let (((), cycles), polls) = block_on(t.measure_async(yield_once));
+    assert_eq!(polls, 2);
+    println!("cycles={cycles:?}");
+}
@@ -0,0 +1,55 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::TSC;
+
+/// A raw TSC reading that can be updated and read from different threads, e.g. a worker's
+/// last-progress timestamp checked by a watchdog. All accesses are `Relaxed`: each load sees
+/// some stored reading, but the cell doesn't order any other memory accesses around it
+#[derive(Debug, Default)]
+pub struct AtomicTsc {
+    value: AtomicU64,
+}
+
+impl AtomicTsc {
+    pub fn new(value: u64) -> Self {
+        Self {
+            value: AtomicU64::new(value),
+        }
+    }
+
+    /// Stores the current reading of `tsc`, with its fence and counter mask
+    #[inline]
+    pub fn store_now(&self, tsc: &TSC) {
+        self.store(tsc.read());
+    }
+
+    #[inline]
+    pub fn store(&self, value: u64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn load(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Nanoseconds since the stored reading, saturating to 0 if it's in the future (e.g. stored
+    /// from a core whose counter runs slightly ahead)
+    pub fn age_ns(&self, tsc: &TSC) -> u64 {
+        tsc.elapsed_ns(self.load())
+    }
+}
+
+#[test]
+fn test_atomic_tsc() {
+    let t = TSC::from_freq(1_000_000_000);
+    let cell = AtomicTsc::default();
+    std::thread::scope(|s| {
+        s.spawn(|| cell.store_now(&t));
+    });
+    assert!(cell.load() > 0);
+    assert!(cell.age_ns(&t) < 60_000_000_000);
+
+    cell.store(u64::MAX);
+    assert_eq!(cell.age_ns(&t), 0);
+}
@@ -0,0 +1,139 @@
+use core::time::Duration;
+use std::time::Instant;
+
+use crate::TSC;
+
+/// Per-iteration timings collected by `TSC::bench()`, in nanoseconds, with the measurement
+/// overhead (see `TSC::estimate_overhead()`) already subtracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchStats {
+    pub iters: usize,
+    pub min_ns: u64,
+    pub median_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+    /// The overhead that was subtracted from each sample, in nanoseconds
+    pub overhead_ns: u64,
+}
+
+/// The spread of back-to-back `read_tsc()` deltas collected by `TSC::probe_jitter()`, in
+/// nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JitterStats {
+    pub samples: usize,
+    pub min_ns: u64,
+    pub median_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+    /// How many deltas were zero cycles, i.e. shorter than the counter's resolution
+    pub zero_deltas: usize,
+}
+
+// the `p`th percentile (nearest rank) of a sorted, nonempty slice
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    sorted[(sorted.len() * p).div_ceil(100).max(1) - 1]
+}
+
+impl TSC {
+    /// Times `iters` calls of `f` individually, after a warmup of a tenth as many untimed calls.
+    /// Samples go into a buffer sized up front, so nothing is allocated while timing. Panics if
+    /// `iters` is zero
+    pub fn bench(&self, iters: usize, mut f: impl FnMut()) -> BenchStats {
+        assert!(iters > 0, "bench() needs at least one iteration");
+        let overhead = Self::estimate_overhead();
+        for _ in 0..iters.div_ceil(10) {
+            core::hint::black_box(&mut f)();
+        }
+
+        let mut samples = Vec::with_capacity(iters);
+        for _ in 0..iters {
+            let ((), cycles) = Self::measure(&mut f);
+            samples.push(cycles.saturating_sub(overhead));
+        }
+        samples.sort_unstable();
+
+        let nth = |p: usize| self.tsc_to_ns(percentile(&samples, p));
+        BenchStats {
+            iters,
+            min_ns: nth(0),
+            median_ns: nth(50),
+            p99_ns: nth(99),
+            max_ns: nth(100),
+            overhead_ns: self.tsc_to_ns(overhead),
+        }
+    }
+
+    /// Takes `samples` pairs of back-to-back `read_tsc()` calls, for a quick look at how finely
+    /// and steadily this machine can time: the median is the cost of a read, a high p99 or max
+    /// points at interrupts or SMIs, and many zero deltas at a coarse counter (e.g. a 24MHz
+    /// timer on aarch64). Panics if `samples` is zero
+    pub fn probe_jitter(&self, samples: usize) -> JitterStats {
+        assert!(samples > 0, "probe_jitter() needs at least one sample");
+        let mut deltas = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let start = Self::read_tsc();
+            deltas.push(Self::read_tsc().saturating_sub(start));
+        }
+        deltas.sort_unstable();
+
+        let nth = |p: usize| self.tsc_to_ns(percentile(&deltas, p));
+        JitterStats {
+            samples,
+            min_ns: nth(0),
+            median_ns: nth(50),
+            p99_ns: nth(99),
+            max_ns: nth(100),
+            zero_deltas: deltas.partition_point(|&delta| delta == 0),
+        }
+    }
+
+    /// The total time of `iters` `now_ns()` calls and of `iters` `Instant::now()` calls, both
+    /// timed with `Instant`, for checking that the TSC pays off on this machine. Each result
+    /// goes through `black_box`, so neither loop can be optimized away
+    pub fn compare_to_instant(&self, iters: usize) -> (Duration, Duration) {
+        let start = Instant::now();
+        for _ in 0..iters {
+            core::hint::black_box(core::hint::black_box(self).now_ns());
+        }
+        let tsc = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..iters {
+            core::hint::black_box(Instant::now());
+        }
+        (tsc, start.elapsed())
+    }
+}
+
+#[test]
+fn test_bench() {
+    let t = TSC::from_freq(2_000_000_000);
+    let mut calls = 0;
+    let stats = t.bench(1000, || calls += 1);
+    assert_eq!(calls, 1100);
+    assert_eq!(stats.iters, 1000);
+    assert!(stats.min_ns <= stats.median_ns);
+    assert!(stats.median_ns <= stats.p99_ns);
+    assert!(stats.p99_ns <= stats.max_ns);
+}
+
+#[test]
+fn test_probe_jitter() {
+    let t = TSC::from_freq(1_000_000_000);
+    let stats = t.probe_jitter(10_000);
+    println!("{stats:?}");
+    assert_eq!(stats.samples, 10_000);
+    assert!(stats.min_ns <= stats.median_ns);
+    assert!(stats.median_ns <= stats.p99_ns);
+    assert!(stats.p99_ns <= stats.max_ns);
+    assert!(stats.zero_deltas <= stats.samples);
+}
+
+#[test]
+fn test_compare_to_instant() {
+    let t = TSC::from_freq(1_000_000_000);
+    let (tsc, instant) = t.compare_to_instant(100_000);
+    println!("now_ns={tsc:?} Instant::now={instant:?}");
+    assert!(tsc > Duration::ZERO);
+    assert!(instant > Duration::ZERO);
+}
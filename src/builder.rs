@@ -0,0 +1,120 @@
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+use crate::{Error, Fence, FreqSource, TSC};
+
+/// Constructs a `TSC` with explicit options, for when `new()`'s defaults don't fit:
+///
+/// ```ignore
+/// let tsc = TscBuilder::new()
+///     .frequency_source(FreqSource::Cpuid15)
+///     .default_fence(Fence::Rdtscp)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TscBuilder {
+    source: Option<FreqSource>,
+    allow_variable: bool,
+    #[cfg(feature = "std")]
+    window: Option<Duration>,
+    fence: Fence,
+    counter_bits: Option<u32>,
+}
+
+impl TscBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the frequency from `source` only, instead of trying every source in order like
+    /// `TSC::cpu_freq()`. `FreqSource::Calibrated` calibrates over the default window unless
+    /// `calibrate()` sets one
+    pub fn frequency_source(mut self, source: FreqSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Accepts a TSC without the invariant bit (see `TSC::new_allow_variable()`)
+    pub fn allow_variable(mut self, allow: bool) -> Self {
+        self.allow_variable = allow;
+        self
+    }
+
+    /// Calibrates against `Instant` over `window` (see `TSC::calibrate()`), rather than reading
+    /// a reported frequency
+    #[cfg(feature = "std")]
+    pub fn calibrate(mut self, window: Duration) -> Self {
+        self.source = Some(FreqSource::Calibrated);
+        self.window = Some(window);
+        self
+    }
+
+    /// The fence the clock's instance methods (`now_ns()`, `start()`, ...) read the counter with
+    pub fn default_fence(mut self, fence: Fence) -> Self {
+        self.fence = fence;
+        self
+    }
+
+    /// Declares the counter narrower than 64 bits (see `TSC::counter_bits()`), so reads get masked
+    /// and deltas across a wrap come out right. Panics unless `bits` is in `1..=64`
+    pub fn counter_bits(mut self, bits: u32) -> Self {
+        assert!(
+            (1..=64).contains(&bits),
+            "counter width must be 1 to 64 bits"
+        );
+        self.counter_bits = Some(bits);
+        self
+    }
+
+    pub fn build(self) -> Result<TSC, Error> {
+        let require_invariant = !self.allow_variable;
+        let (freq, source) = match self.source {
+            #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+            None => TSC::detect_freq(require_invariant)?,
+            #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+            None => (TSC::cpu_freq()?, FreqSource::Platform),
+            #[cfg(feature = "std")]
+            Some(FreqSource::Calibrated) => {
+                let window = self.window.unwrap_or(TSC::DEFAULT_CALIBRATION_WINDOW);
+                (TSC::calibrate(window)?.get_freq(), FreqSource::Calibrated)
+            }
+            Some(source) => (TSC::freq_from_source(source, require_invariant)?, source),
+        };
+        let tsc = TSC {
+            fence: self.fence,
+            counter_bits: self.counter_bits.unwrap_or(64),
+            ..TSC::from_detected(freq, source)
+        };
+        Ok(tsc.with_start_now())
+    }
+}
+
+#[test]
+fn test_builder() {
+    let t = TscBuilder::new()
+        .calibrate(Duration::from_millis(10))
+        .default_fence(Fence::MfenceLfence)
+        .build()
+        .unwrap();
+    assert_eq!(t.default_fence(), Fence::MfenceLfence);
+    let n0 = t.now_ns();
+    assert!(t.now_ns() >= n0);
+
+    match TSC::new() {
+        Ok(detected) => {
+            let built = TSC::builder().build().unwrap();
+            assert_eq!(built.get_freq(), detected.get_freq());
+            assert_eq!(built.default_fence(), TSC::DEFAULT_FENCE);
+        }
+        Err(err) => assert_eq!(TSC::builder().build().unwrap_err(), err),
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    assert_eq!(
+        TSC::builder()
+            .frequency_source(FreqSource::Cpuid15)
+            .build()
+            .unwrap_err(),
+        Error::SourceUnavailable(FreqSource::Cpuid15)
+    );
+}
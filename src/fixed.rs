@@ -0,0 +1,72 @@
+use core::time::Duration;
+
+use crate::TSC;
+
+/// A clock whose frequency (in Hz) is fixed at compile time, for targets where it's known at
+/// build time (e.g. firmware on a fixed-clock part). It's zero-sized: the conversion factors are
+/// constants the compiler folds into each call site, so converting loads no fields. Conversions
+/// match `TSC::from_freq(FREQ)` exactly; `FREQ` must be nonzero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FixedTsc<const FREQ: u64>;
+
+impl<const FREQ: u64> FixedTsc<FREQ> {
+    const TSC: TSC = TSC::from_freq(FREQ);
+
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// The equivalent runtime clock
+    pub const fn tsc(&self) -> TSC {
+        Self::TSC
+    }
+
+    /// The current time in nanoseconds, from `TSC::read_tsc()`
+    #[inline]
+    pub fn now_ns(&self) -> u64 {
+        self.tsc_to_ns(TSC::read_tsc())
+    }
+
+    #[inline(always)]
+    pub const fn tsc_to_ns(&self, raw: u64) -> u64 {
+        Self::TSC.tsc_to_ns(raw)
+    }
+
+    pub fn tsc_to_duration(&self, raw: u64) -> Duration {
+        Self::TSC.tsc_to_duration(raw)
+    }
+
+    /// Nanoseconds since `start_tsc`, saturating to 0 if the counter appears to have gone backwards
+    #[inline]
+    pub fn elapsed_ns(&self, start_tsc: u64) -> u64 {
+        self.tsc_to_ns(TSC::read_tsc().saturating_sub(start_tsc))
+    }
+}
+
+#[test]
+fn test_fixed_tsc() {
+    use std::time::Instant;
+    const CLOCK: FixedTsc<2_400_000_000> = FixedTsc::new();
+    let t = TSC::from_freq(2_400_000_000);
+    assert_eq!(CLOCK.tsc(), t);
+    for raw in [0, 1, 2_400_000_000, 0x1234_5678_9abc_def0, u64::MAX] {
+        assert_eq!(CLOCK.tsc_to_ns(raw), t.tsc_to_ns(raw));
+        assert_eq!(CLOCK.tsc_to_duration(raw), t.tsc_to_duration(raw));
+    }
+    const { assert!(FixedTsc::<1_000>::new().tsc_to_ns(1_500) == 1_500_000_000) };
+
+    // timings against the field-based clock; printed only, since they depend on the machine
+    let t0 = Instant::now();
+    let mut counter = 0;
+    for _ in 0..100_000 {
+        counter += core::hint::black_box(&t).now_ns() >> 32;
+    }
+    let field = t0.elapsed();
+    let t0 = Instant::now();
+    for _ in 0..100_000 {
+        counter += core::hint::black_box(&CLOCK).now_ns() >> 32;
+    }
+    let fixed = t0.elapsed();
+    assert!(counter > 0);
+    println!("field={field:?} fixed={fixed:?}");
+}
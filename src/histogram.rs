@@ -0,0 +1,140 @@
+use crate::TSC;
+
+// each power of two is split into HALF linear sub-buckets (values below 2 * HALF map to their own
+// bucket), bounding the relative error of a bucket to 1 / HALF
+const SUB_BITS: u32 = 5;
+const HALF: usize = 1 << (SUB_BITS - 1);
+const BUCKETS: usize = (66 - SUB_BITS as usize) << (SUB_BITS - 1);
+
+/// A fixed-size, log-scaled histogram of cycle counts. Recording never allocates and costs a
+/// handful of instructions; percentiles are converted to nanoseconds using the held `TSC`
+#[derive(Debug, Clone)]
+pub struct TscHistogram {
+    tsc: TSC,
+    counts: [u64; BUCKETS],
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl TscHistogram {
+    pub fn new(tsc: TSC) -> Self {
+        Self {
+            tsc,
+            counts: [0; BUCKETS],
+            total: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    #[inline]
+    fn bucket_of(cycles: u64) -> usize {
+        let bits = 64 - (cycles | (2 * HALF as u64 - 1)).leading_zeros();
+        let shift = bits - SUB_BITS;
+        ((shift as usize) << (SUB_BITS - 1)) + (cycles >> shift) as usize
+    }
+
+    // the largest value that maps to `bucket`
+    fn bucket_high(bucket: usize) -> u64 {
+        if bucket < 2 * HALF {
+            return bucket as u64;
+        }
+        let shift = bucket / HALF - 1;
+        let low = ((bucket % HALF + HALF) as u64) << shift;
+        low + ((1u64 << shift) - 1)
+    }
+
+    #[inline]
+    pub fn record(&mut self, cycles: u64) {
+        self.counts[Self::bucket_of(cycles)] += 1;
+        self.total += 1;
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min_ns(&self) -> u64 {
+        if self.total == 0 {
+            0
+        } else {
+            self.tsc.tsc_to_ns(self.min)
+        }
+    }
+
+    pub fn max_ns(&self) -> u64 {
+        self.tsc.tsc_to_ns(self.max)
+    }
+
+    /// The `p`th percentile (`p` in 0..=100) in nanoseconds, accurate to the bucket resolution
+    /// (~6%). Returns 0 for an empty histogram
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+        // ceil() isn't available in core
+        let exact = p.clamp(0.0, 100.0) / 100.0 * self.total as f64;
+        let rank = (exact as u64 + ((exact as u64 as f64) < exact) as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let cycles = Self::bucket_high(bucket).clamp(self.min, self.max);
+                return self.tsc.tsc_to_ns(cycles);
+            }
+        }
+        self.tsc.tsc_to_ns(self.max)
+    }
+
+    pub fn merge(&mut self, other: &TscHistogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.tsc);
+    }
+}
+
+#[test]
+fn test_buckets() {
+    let mut prev = 0;
+    for v in (0..100_000).chain([u64::MAX / 3, u64::MAX - 1, u64::MAX]) {
+        let b = TscHistogram::bucket_of(v);
+        assert!(b >= prev && b < BUCKETS);
+        assert!(TscHistogram::bucket_high(b) >= v);
+        assert!(v == 0 || TscHistogram::bucket_high(b - 1) < v);
+        prev = b;
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let mut h = TscHistogram::new(TSC::from_freq(1_000_000_000));
+    assert_eq!(h.percentile(50.0), 0);
+    for v in 1..=1000 {
+        h.record(v);
+    }
+    assert_eq!(h.count(), 1000);
+    assert_eq!(h.min_ns(), 1);
+    assert_eq!(h.percentile(0.0), 1);
+    assert_eq!(h.percentile(100.0), 1000);
+    assert!(h.percentile(50.0).abs_diff(500) <= 500 / 16);
+    assert!(h.percentile(99.0).abs_diff(990) <= 990 / 16);
+
+    let mut other = TscHistogram::new(TSC::from_freq(1_000_000_000));
+    other.record(5000);
+    h.merge(&other);
+    assert_eq!(h.count(), 1001);
+    assert_eq!(h.max_ns(), 5000);
+
+    h.reset();
+    assert_eq!(h.count(), 0);
+}
@@ -0,0 +1,116 @@
+use core::time::Duration;
+#[cfg(any(test, feature = "clock-trait"))]
+use std::time::Instant;
+
+use crate::TSC;
+
+/// A TSC-based stand-in for `std::time::Instant`. Since the counter's frequency lives in the
+/// `TSC`, the methods that produce a `Duration` take it as an argument
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TscInstant(u64);
+
+impl TscInstant {
+    #[inline(always)]
+    pub fn now(tsc: &TSC) -> Self {
+        Self(tsc.start())
+    }
+
+    pub fn elapsed(&self, tsc: &TSC) -> Duration {
+        tsc.elapsed_duration(self.0)
+    }
+
+    /// Like `Instant::duration_since()`, saturates to zero if `earlier` is later than `self`. On
+    /// a counter narrower than 64 bits, a wrap between the two counts as time passing, like in
+    /// `elapsed()`
+    pub fn duration_since(&self, earlier: TscInstant, tsc: &TSC) -> Duration {
+        tsc.tsc_to_duration(tsc.cycles_between(earlier.0, self.0).unwrap_or(0))
+    }
+
+    pub fn as_raw(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Abstracts over `Instant` and `TscInstant`, so generic timing code can be parameterized over the
+/// clock. `Source` is whatever the instant needs to be read and converted: nothing for `Instant`,
+/// a `TSC` for `TscInstant`
+#[cfg(feature = "clock-trait")]
+pub trait Clock: Copy + Ord {
+    type Source;
+
+    fn now(source: &Self::Source) -> Self;
+    fn duration_since(&self, earlier: Self, source: &Self::Source) -> Duration;
+
+    fn elapsed(&self, source: &Self::Source) -> Duration {
+        Self::now(source).duration_since(*self, source)
+    }
+}
+
+#[cfg(feature = "clock-trait")]
+impl Clock for Instant {
+    type Source = ();
+
+    fn now(_: &()) -> Self {
+        Instant::now()
+    }
+
+    fn duration_since(&self, earlier: Self, _: &()) -> Duration {
+        Instant::duration_since(self, earlier)
+    }
+}
+
+#[cfg(feature = "clock-trait")]
+impl Clock for TscInstant {
+    type Source = TSC;
+
+    fn now(source: &TSC) -> Self {
+        TscInstant::now(source)
+    }
+
+    fn duration_since(&self, earlier: Self, source: &TSC) -> Duration {
+        TscInstant::duration_since(self, earlier, source)
+    }
+}
+
+#[test]
+fn test_tsc_instant() {
+    let t = TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap();
+    let (i0, t0) = (TscInstant::now(&t), Instant::now());
+    std::thread::sleep(Duration::from_millis(20));
+    let (i1, t1) = (TscInstant::now(&t), Instant::now());
+
+    let tsc_dt = i1.duration_since(i0, &t);
+    let clock_dt = t1.duration_since(t0);
+    assert!(
+        tsc_dt.abs_diff(clock_dt) < clock_dt / 20,
+        "{tsc_dt:?} {clock_dt:?}"
+    );
+    assert_eq!(i0.duration_since(i1, &t), Duration::ZERO);
+    assert!(i0.elapsed(&t) >= tsc_dt);
+
+    let narrow = TSC {
+        counter_bits: 8,
+        ..TSC::from_freq(1_000_000_000)
+    };
+    let (before, after) = (TscInstant(250), TscInstant(4));
+    assert_eq!(
+        after.duration_since(before, &narrow),
+        Duration::from_nanos(10)
+    );
+    assert_eq!(before.duration_since(after, &narrow), Duration::ZERO);
+}
+
+#[cfg(feature = "clock-trait")]
+#[test]
+fn test_clock_trait() {
+    fn time<C: Clock>(source: &C::Source) -> Duration {
+        let start = C::now(source);
+        std::thread::sleep(Duration::from_millis(5));
+        start.elapsed(source)
+    }
+
+    assert!(time::<Instant>(&()) >= Duration::from_millis(5));
+    let t = TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap();
+    assert!(time::<TscInstant>(&t) >= Duration::from_millis(4));
+}
@@ -1,72 +1,1094 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(all(
+    target_arch = "wasm32",
+    not(any(feature = "wasm", feature = "fallback"))
+))]
+compile_error!("tsc: wasm32 has no TSC; enable the `wasm` feature to use `performance.now()`");
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "x86",
+    target_arch = "aarch64",
+    target_arch = "riscv64",
+    target_arch = "wasm32",
+    feature = "fallback"
+)))]
+compile_error!(
+    "tsc: unsupported target architecture; enable the `fallback` feature to time with `Instant`"
+);
+
+#[cfg(any(
+    all(feature = "fence-lfence", feature = "fence-rdtscp"),
+    all(feature = "fence-lfence", feature = "fence-none"),
+    all(feature = "fence-rdtscp", feature = "fence-none")
+))]
+compile_error!(
+    "tsc: `fence-lfence`, `fence-rdtscp` and `fence-none` are mutually exclusive; disable default features to pick one"
+);
+
+// the intrinsics used below exist under the same names on both x86 flavors
+#[cfg(target_arch = "x86")]
+use core::arch::x86 as arch;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64 as arch;
+
+#[cfg(feature = "std")]
+mod abs;
+#[cfg(feature = "std")]
+mod anchored;
+#[cfg(feature = "async")]
+mod async_impl;
+mod atomic;
+#[cfg(feature = "std")]
+mod bench;
+mod builder;
+#[cfg(feature = "criterion")]
+mod criterion_impl;
+mod fixed;
+mod histogram;
+#[cfg(feature = "std")]
+mod instant;
+#[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+mod mach;
+mod monotonic;
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+mod perf;
+#[cfg(feature = "quanta-compat")]
+pub mod quanta;
+mod rate;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod source;
+mod span;
+#[cfg(feature = "std")]
+mod sync;
+mod timestamp;
+mod trace;
+#[cfg(feature = "tracing")]
+mod tracing_impl;
+
+#[cfg(feature = "std")]
+pub use abs::AbsTsc;
+#[cfg(feature = "std")]
+pub use anchored::AnchoredTSC;
+pub use atomic::AtomicTsc;
+#[cfg(feature = "std")]
+pub use bench::{BenchStats, JitterStats};
+pub use builder::TscBuilder;
+#[cfg(feature = "criterion")]
+pub use criterion_impl::TscMeasurement;
+pub use fixed::FixedTsc;
+pub use histogram::TscHistogram;
+#[cfg(feature = "clock-trait")]
+pub use instant::Clock;
+#[cfg(feature = "std")]
+pub use instant::TscInstant;
+pub use monotonic::MonotonicTsc;
+pub use rate::RateMeter;
+pub use source::{ManualTsc, RealTsc, TscSource};
+pub use span::{Span, SpanSink};
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use sync::TscOffsets;
+pub use timestamp::{Tsc, TscDelta};
+pub use trace::TscTrace;
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
     TscNotSupported,
     InvariantTscNotSupported,
     CpuidLeafTscFailed,
     CpuidLeafFreqFailed,
+    CalibrationFailed,
+    RdtscpNotSupported,
+    TimebaseFreqUnavailable,
+    QueryPerformanceFrequencyFailed,
+    HypervisorLeafFailed,
+    ImplausibleFrequency(u64),
+    SourceUnavailable(FreqSource),
+    /// A measurement was asked for zero samples
+    NoSamples,
+    /// `self_check()` converted `ns` to ticks and back and got `round_trip`
+    RoundTripMismatch {
+        ns: u64,
+        round_trip: u64,
+    },
+    /// `self_check()` read `after` from `now_ns()` following `before`
+    NotMonotonic {
+        before: u64,
+        after: u64,
+    },
 }
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        <Self as std::fmt::Debug>::fmt(self, f)
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TscNotSupported => f.write_str("the CPU has no TSC"),
+            Self::InvariantTscNotSupported => f.write_str("the CPU's TSC is not invariant"),
+            Self::CpuidLeafTscFailed => f.write_str("CPUID leaf 0x15 doesn't report a TSC ratio"),
+            Self::CpuidLeafFreqFailed => f.write_str("CPUID leaf 0x16 doesn't report a frequency"),
+            Self::CalibrationFailed => f.write_str("calibrating the TSC against the clock failed"),
+            Self::RdtscpNotSupported => f.write_str("the CPU has no rdtscp instruction"),
+            Self::TimebaseFreqUnavailable => f.write_str("the timebase frequency is unavailable"),
+            Self::QueryPerformanceFrequencyFailed => {
+                f.write_str("QueryPerformanceFrequency failed")
+            }
+            Self::HypervisorLeafFailed => {
+                f.write_str("the hypervisor doesn't report a TSC frequency")
+            }
+            Self::ImplausibleFrequency(freq) => write!(f, "implausible TSC frequency: {freq} Hz"),
+            Self::SourceUnavailable(source) => {
+                write!(f, "frequency source {source:?} is unavailable")
+            }
+            Self::NoSamples => f.write_str("a measurement needs at least one sample"),
+            Self::RoundTripMismatch { ns, round_trip } => {
+                write!(
+                    f,
+                    "{ns}ns converted to TSC ticks and back gave {round_trip}ns"
+                )
+            }
+            Self::NotMonotonic { before, after } => {
+                write!(f, "now_ns() went backwards from {before} to {after}")
+            }
+        }
     }
 }
 
-impl std::error::Error for Error {}
+// `core::error::Error` is the same trait as `std::error::Error`, so this also covers `std` users
+impl core::error::Error for Error {}
+
+/// The TSC-related features of the current CPU, each probed independently so callers can make
+/// their own policy decisions (e.g. accepting a non-invariant TSC on a pinned thread)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TscCaps {
+    pub has_tsc: bool,
+    /// CPUID 0x80000007 edx bit 8, which the vendors define as both constant-rate and nonstop
+    pub has_invariant_tsc: bool,
+    /// Whether the kernel considers the TSC rate independent of P-states (Linux's `constant_tsc`
+    /// flag, also inferred from the CPU model); `None` where this can't be read
+    pub has_constant_tsc: Option<bool>,
+    /// Whether the kernel considers the TSC to keep running in deep C-states (Linux's
+    /// `nonstop_tsc` flag); `None` where this can't be read
+    pub has_nonstop_tsc: Option<bool>,
+    pub has_rdtscp: bool,
+    pub freq: Option<u64>,
+}
 
+/// The outcome of `TSC::calibrate_refined()`
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    pub tsc: TSC,
+    /// A bound on the frequency error in parts per million, from how tightly `Instant` bracketed
+    /// the TSC reads of the chosen round
+    pub ppm_error: f64,
+}
+
+/// The raw CPUID registers the frequency detection relies on, for diagnosing misdetection
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuidReport {
+    pub leaf_1_edx: u32,
+    pub leaf_80000007_edx: u32,
+    pub leaf_15_eax: u32,
+    pub leaf_15_ebx: u32,
+    pub leaf_15_ecx: u32,
+    pub leaf_16_eax: u32,
+}
+
+/// How a TSC read is ordered relative to the surrounding instructions (see `TSC::read_tsc_with()`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fence {
+    None,
+    Lfence,
+    MfenceLfence,
+    Rdtscp,
+}
+
+/// `TSC::DEFAULT_FENCE`, the one picked by the `fence-*` features
+impl Default for Fence {
+    fn default() -> Self {
+        TSC::DEFAULT_FENCE
+    }
+}
+
+/// Where the frequency of a clock came from (see `TSC::new_with_source()` and `TSC::freq_source()`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreqSource {
+    /// CPUID leaf 0x15, the crystal clock times the TSC ratio: the actual rate, to within the
+    /// crystal's tolerance
+    Cpuid15,
+    /// CPUID leaf 0x16, the nominal base frequency in whole MHz (see `TSC::is_nominal()`)
+    Cpuid16,
+    /// The hypervisor's timing leaf 0x40000010
+    Hypervisor,
+    /// The OS kernel's own calibration (see `TSC::freq_from_kernel()`)
+    Kernel,
+    /// Timed against another clock (see `TSC::calibrate()`)
+    Calibrated,
+    /// The platform's architectural timer frequency on non-x86 targets (e.g. `cntfrq_el0`)
+    Platform,
+}
+
+/// A TSC of known frequency, converting counter reads to time. It holds only immutable
+/// conversion factors, so it is guaranteed to be `Send + Sync` (and `Copy`): it can be copied
+/// into each thread, or a single instance shared by reference, e.g. through `TSC::global()`.
+/// Equality and hashing consider only the rate, so clocks calibrated to the same one are
+/// interchangeable as keys whatever their base counter value, fence or width
+#[derive(Clone, Copy)]
 pub struct TSC {
+    // the frequency in Hz, rounded to the nearest integer if the tick length isn't a whole
+    // fraction of a second (see `from_ns_per_tick()`)
     freq: u64,
+    // nanoseconds per tick as the exact fraction `tick_numer / tick_denom`, in lowest terms;
+    // `1e9 / freq` for clocks built from a frequency. Conversions go through this, not `freq`
+    tick_numer: u64,
+    tick_denom: u64,
+    // nanoseconds per cycle as a fixed-point number with `NS_MULT_SHIFT` fractional bits, so that
+    // converting needs only multiplications (see `tsc_to_ns()`)
+    ns_mult_int: u64,
+    ns_mult_frac: u64,
+    // the counter when the clock was constructed, for `now_f64_since_start()`
+    start: u64,
+    // how the instance methods (`now_ns()`, `start()`, ...) read the counter
+    fence: Fence,
+    // the counter's width; reads are masked to it and deltas taken modulo it
+    counter_bits: u32,
+    // where `freq` came from; `None` if it was passed in (e.g. to `from_freq()`)
+    source: Option<FreqSource>,
+    // the 1-sigma uncertainty of `freq` in ppm, where calibration estimated one
+    uncertainty_ppm: Option<f64>,
+}
+
+// part of the public contract; fails to compile if a non-thread-safe field is ever added
+fn _assert_send_sync()
+where
+    TSC: Send + Sync,
+{
 }
 
 impl TSC {
+    #[must_use = "this returns the clock rather than storing it anywhere"]
     pub fn new() -> Result<Self, Error> {
-        let freq = Self::cpu_freq()?;
-        Ok(Self { freq })
+        let (freq, source) = Self::detect()?;
+        Self::log_detected(freq, source);
+        Ok(Self::from_detected(freq, source))
+    }
+
+    /// Like `new()`, but also reports which source the frequency came from, falling back to
+    /// `calibrate()` with the default window when no source reports one (with `std`)
+    pub fn new_with_source() -> Result<(Self, FreqSource), Error> {
+        let (tsc, source) = match Self::detect() {
+            Ok((freq, source)) => (Self::from_detected(freq, source), source),
+            #[cfg(feature = "std")]
+            Err(
+                Error::CpuidLeafTscFailed
+                | Error::CpuidLeafFreqFailed
+                | Error::HypervisorLeafFailed
+                | Error::QueryPerformanceFrequencyFailed
+                | Error::TimebaseFreqUnavailable
+                | Error::ImplausibleFrequency(_),
+            ) => (
+                Self::calibrate(Self::DEFAULT_CALIBRATION_WINDOW)?,
+                FreqSource::Calibrated,
+            ),
+            Err(err) => return Err(err),
+        };
+        Self::log_detected(tsc.freq, source);
+        Ok((tsc, source))
+    }
+
+    fn detect() -> Result<(u64, FreqSource), Error> {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        return Self::detect_freq(true);
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+        return Self::cpu_freq().map(|freq| (freq, FreqSource::Platform));
+    }
+
+    // the clock for a detected frequency, starting now
+    #[cfg(not(all(target_arch = "aarch64", target_os = "macos")))]
+    fn from_detected(freq: u64, source: FreqSource) -> Self {
+        Self::from_freq(freq).with_source(source).with_start_now()
+    }
+
+    // the timebase is an exact ns-per-tick ratio, which `cpu_freq()` can only round to Hz
+    #[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+    fn from_detected(freq: u64, source: FreqSource) -> Self {
+        let tsc = match (source, mach::timebase()) {
+            (FreqSource::Platform, Some((numer, denom))) => {
+                Self::from_ratio(freq, numer as u64, denom as u64)
+            }
+            _ => Self::from_freq(freq),
+        };
+        tsc.with_source(source).with_start_now()
+    }
+
+    // one info line per process, however many clocks get constructed
+    #[cfg(feature = "log")]
+    fn log_detected(freq: u64, source: FreqSource) {
+        use core::sync::atomic::{AtomicBool, Ordering};
+        static LOGGED: AtomicBool = AtomicBool::new(false);
+
+        if !LOGGED.swap(true, Ordering::Relaxed) {
+            log::info!("tsc: freq={freq} Hz source={source:?}");
+        }
+    }
+
+    #[cfg(not(feature = "log"))]
+    #[inline(always)]
+    fn log_detected(_freq: u64, _source: FreqSource) {}
+
+    /// Constructs the clock from a frequency (in Hz) known ahead of time, skipping CPUID probing.
+    /// Panics if `freq` is zero (at compile time when used in a `const`). Being `const`, it can't
+    /// read the counter, so `now_f64_since_start()` counts from 0 unless `with_start_now()` is
+    /// applied
+    #[must_use]
+    pub const fn from_freq(freq: u64) -> Self {
+        assert!(freq != 0, "TSC frequency must be nonzero");
+        Self::from_ratio(freq, 1_000_000_000, freq)
+    }
+
+    /// Constructs the clock from the length of a tick, `numer / denom` nanoseconds, kept exact
+    /// for every conversion rather than rounded to a frequency in Hz. This is the natural form
+    /// when the timebase isn't a whole number of Hz, e.g. `mach_timebase_info()`'s ratio. Fails
+    /// for a zero `denom`, or a tick shorter than 1ps or longer than 1s
+    pub fn from_ns_per_tick(numer: u64, denom: u64) -> Result<Self, Error> {
+        if denom == 0 {
+            return Err(Error::ImplausibleFrequency(0));
+        }
+        // the frequency is 1e9 * denom / numer, rounded to nearest
+        let hz = (1_000_000_000 * denom as u128 + numer as u128 / 2)
+            .checked_div(numer as u128)
+            .unwrap_or(u128::MAX);
+        match u64::try_from(hz) {
+            Ok(freq @ 1..=1_000_000_000_000) if numer as u128 <= 1_000_000_000 * denom as u128 => {
+                Ok(Self::from_ratio(freq, numer, denom).with_start_now())
+            }
+            _ => Err(Error::ImplausibleFrequency(
+                hz.try_into().unwrap_or(u64::MAX),
+            )),
+        }
+    }
+
+    // `numer` and `denom` must be nonzero, and `numer / denom` at most 1e9
+    const fn from_ratio(freq: u64, numer: u64, denom: u64) -> Self {
+        let gcd = gcd(numer, denom);
+        let (numer, denom) = (numer / gcd, denom / gcd);
+        let ns_mult = ((numer as u128) << NS_MULT_SHIFT) / denom as u128;
+        Self {
+            freq,
+            tick_numer: numer,
+            tick_denom: denom,
+            ns_mult_int: (ns_mult >> NS_MULT_SHIFT) as u64,
+            ns_mult_frac: ns_mult as u64,
+            start: 0,
+            fence: Self::DEFAULT_FENCE,
+            counter_bits: 64,
+            source: None,
+            uncertainty_ppm: None,
+        }
+    }
+
+    const fn with_source(self, source: FreqSource) -> Self {
+        Self {
+            source: Some(source),
+            ..self
+        }
+    }
+
+    /// Makes the current counter value the base of `now_f64_since_start()`. The runtime
+    /// constructors (`new()`, `calibrate()`, ...) already do this
+    pub fn with_start_now(self) -> Self {
+        Self {
+            start: self.read(),
+            ..self
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub const DEFAULT_CALIBRATION_WINDOW: Duration = Duration::from_millis(100);
+
+    /// Derives the frequency empirically by counting TSC ticks across a sleep of `window`,
+    /// as measured by `Instant`. Useful when the CPUID-reported frequency is missing or wrong
+    #[cfg(feature = "std")]
+    #[must_use = "this returns the clock rather than storing it anywhere"]
+    pub fn calibrate(window: Duration) -> Result<Self, Error> {
+        // pinned, so both reads come from the same core's counter
+        let (c0, c1, elapsed) = Self::on_current_core(|| {
+            let t0 = Instant::now();
+            let c0 = Self::read_tsc();
+            std::thread::sleep(window);
+            let c1 = Self::read_tsc();
+            (c0, c1, t0.elapsed().as_nanos())
+        });
+
+        if elapsed == 0 || c1 <= c0 {
+            return Err(Error::CalibrationFailed);
+        }
+        let freq = ((c1 - c0) as u128 * 1_000_000_000 / elapsed) as u64;
+        if freq == 0 {
+            return Err(Error::CalibrationFailed);
+        }
+        Ok(Self::from_freq(freq)
+            .with_source(FreqSource::Calibrated)
+            .with_start_now())
+    }
+
+    /// Like `calibrate()`, but measures the sleep with `clock_gettime(CLOCK_MONOTONIC)`, the
+    /// clock NTP slews to keep in step with the disciplined system time. The frequency comes
+    /// out in NTP-corrected seconds, so conversions agree with the system clock rather than
+    /// with the raw crystal (which is what `monotonic_raw_ns()` follows). On Linux `Instant` is
+    /// itself `CLOCK_MONOTONIC`, so this matches `calibrate()` there; it differs on platforms
+    /// like macOS, whose `Instant` is an unslewed clock
+    #[cfg(all(unix, feature = "std"))]
+    #[must_use = "this returns the clock rather than storing it anywhere"]
+    pub fn calibrate_against_monotonic(window: Duration) -> Result<Self, Error> {
+        let (c0, c1, elapsed) = Self::on_current_core(|| {
+            let t0 = Self::clock_ns(libc::CLOCK_MONOTONIC);
+            let c0 = Self::read_tsc();
+            std::thread::sleep(window);
+            let c1 = Self::read_tsc();
+            (
+                c0,
+                c1,
+                Self::clock_ns(libc::CLOCK_MONOTONIC).saturating_sub(t0),
+            )
+        });
+
+        if elapsed == 0 || c1 <= c0 {
+            return Err(Error::CalibrationFailed);
+        }
+        let freq = ((c1 - c0) as u128 * 1_000_000_000 / elapsed as u128) as u64;
+        if freq == 0 {
+            return Err(Error::CalibrationFailed);
+        }
+        Ok(Self::from_freq(freq)
+            .with_source(FreqSource::Calibrated)
+            .with_start_now())
+    }
+
+    /// A `TscBuilder` for choosing the frequency source, fence and other options explicitly
+    pub fn builder() -> TscBuilder {
+        TscBuilder::new()
+    }
+
+    /// Like `calibrate()`, but runs `rounds` rounds and keeps the one whose TSC reads were most
+    /// tightly bracketed by `Instant` reads, i.e. the one least disturbed by preemption. With
+    /// two or more rounds, the spread of their frequencies becomes the clock's
+    /// `freq_uncertainty_ppm()`
+    #[cfg(feature = "std")]
+    pub fn calibrate_refined(rounds: usize, window: Duration) -> Result<Calibration, Error> {
+        // (freq, bracketing gap, elapsed), all in ns but the freq
+        let mut best: Option<(u64, u128, u128)> = None;
+        let mut freqs = Vec::with_capacity(rounds);
+        for _ in 0..rounds {
+            let (t0, c0, gap0) = Self::bracketed_read();
+            std::thread::sleep(window);
+            let (t1, c1, gap1) = Self::bracketed_read();
+            let elapsed = (t1 - t0).as_nanos();
+            let gap = gap0 + gap1;
+            if elapsed == 0 || c1 <= c0 {
+                continue;
+            }
+            let freq = ((c1 - c0) as u128 * 1_000_000_000 / elapsed) as u64;
+            if freq == 0 {
+                continue;
+            }
+            freqs.push(freq as f64);
+            if best.is_none_or(|(_, best_gap, _)| gap < best_gap) {
+                best = Some((freq, gap, elapsed));
+            }
+        }
+
+        let (freq, gap, elapsed) = best.ok_or(Error::CalibrationFailed)?;
+        // the sample standard deviation of the rounds, relative to their mean
+        let uncertainty_ppm = (freqs.len() >= 2).then(|| {
+            let n = freqs.len() as f64;
+            let mean = freqs.iter().sum::<f64>() / n;
+            let variance = freqs.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            variance.sqrt() * 1_000_000.0 / mean
+        });
+        Ok(Calibration {
+            tsc: Self {
+                uncertainty_ppm,
+                ..Self::from_freq(freq).with_source(FreqSource::Calibrated)
+            }
+            .with_start_now(),
+            // each read is off by at most half its gap from the midpoint `Instant`
+            ppm_error: (gap as f64 / 2.0) * 1_000_000.0 / elapsed as f64,
+        })
+    }
+
+    /// Measures the TSC rate against `Instant` over `window` and returns how far it deviates from
+    /// the stored frequency, in (signed) parts per million; positive means the TSC runs faster
+    /// than assumed. A large deviation calls for recalibrating
+    #[cfg(feature = "std")]
+    pub fn verify_freq(&self, window: Duration) -> Result<f64, Error> {
+        let (t0, c0, _) = Self::bracketed_read();
+        std::thread::sleep(window);
+        let (t1, c1, _) = Self::bracketed_read();
+        let elapsed = (t1 - t0).as_nanos();
+        if elapsed == 0 || c1 <= c0 {
+            return Err(Error::CalibrationFailed);
+        }
+        let observed = (c1 - c0) as f64 * 1_000_000_000.0 / elapsed as f64;
+        let freq = self.freq_f64();
+        Ok((observed - freq) * 1_000_000.0 / freq)
+    }
+
+    /// Checks the clock's invariants, for failing fast at startup on a misdetected frequency or
+    /// a broken build: that nanoseconds converted to ticks and back (`ns_to_tsc()`, then
+    /// `tsc_to_ns()`) come back within a tick plus 1ns, across spans from 1ns to a year, and that
+    /// `now_ns()` doesn't go backwards over a burst of reads
+    pub fn self_check(&self) -> Result<(), Error> {
+        const BURST: usize = 1_000;
+        const YEAR_NS: u64 = 365 * 24 * 3600 * 1_000_000_000;
+
+        // converting to ticks truncates up to a tick, and back to ns up to 1ns more
+        let tolerance = self.tick_numer.div_ceil(self.tick_denom) + 1;
+        let mut ns = 1;
+        while ns <= YEAR_NS {
+            for ns in [ns, ns + ns / 3] {
+                let round_trip = self.tsc_to_ns(self.ns_to_tsc(ns));
+                if round_trip > ns || ns - round_trip > tolerance {
+                    return Err(Error::RoundTripMismatch { ns, round_trip });
+                }
+            }
+            ns *= 7;
+        }
+
+        let mut before = self.now_ns();
+        for _ in 0..BURST {
+            let after = self.now_ns();
+            if after < before {
+                return Err(Error::NotMonotonic { before, after });
+            }
+            before = after;
+        }
+        Ok(())
+    }
+
+    /// The kernel's `CLOCK_MONOTONIC_RAW` in nanoseconds: hardware time not slewed by NTP, the
+    /// natural reference for checking the calibrated frequency for drift against `now_ns()`.
+    /// Each call is a `clock_gettime()` (a vDSO call on Linux), roughly 20 to 50 times slower
+    /// than `read_tsc()`. Where the clock doesn't exist (e.g. on the BSDs) this reads
+    /// `CLOCK_MONOTONIC` instead
+    #[cfg(unix)]
+    pub fn monotonic_raw_ns() -> u64 {
+        #[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+        const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC_RAW;
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+        const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+        Self::clock_ns(CLOCK)
     }
 
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(unix)]
+    fn clock_ns(clock: libc::clockid_t) -> u64 {
+        let mut ts: libc::timespec = unsafe { core::mem::zeroed() };
+        unsafe { libc::clock_gettime(clock, &mut ts) };
+        ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+    }
+
+    // a TSC read between two `Instant` reads, timestamped at their midpoint
+    #[cfg(feature = "std")]
+    fn bracketed_read() -> (Instant, u64, u128) {
+        let before = Instant::now();
+        let tsc = Self::read_tsc();
+        let gap = before.elapsed();
+        (before + gap / 2, tsc, gap.as_nanos())
+    }
+
+    /// A process-wide clock, initialized on first use. Detection goes through
+    /// `new_with_source()`, which only falls back to `calibrate()` if the frequency can't be
+    /// probed; panics if the TSC is missing or not invariant, or the calibration fails.
+    /// Initialization runs exactly once even under contention, and the returned reference can be
+    /// freely shared across threads
+    #[cfg(feature = "std")]
+    pub fn global() -> &'static TSC {
+        static GLOBAL: std::sync::OnceLock<TSC> = std::sync::OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            Self::new_with_source()
+                .map(|(tsc, _)| tsc)
+                .expect("failed to determine the TSC frequency")
+        })
+    }
+
+    /// A per-thread copy of `global()`, so hot paths get the clock by value without touching shared
+    /// state. Detection still happens once per process: threads after the first only copy the
+    /// conversion factors. Panics like `global()`
+    #[cfg(feature = "std")]
+    pub fn thread_local() -> TSC {
+        std::thread_local! {
+            static LOCAL: TSC = *TSC::global();
+        }
+        LOCAL.with(|tsc| *tsc)
+    }
+
+    /// Like `new()`, but accepts a TSC that lacks the invariant bit, i.e. one whose rate follows
+    /// the core's P-state. Timestamps are only meaningful if the core frequency is held fixed
+    /// (e.g. by pinning the cpufreq governor and disabling turbo) and the core doesn't enter
+    /// C-states that stop the TSC; otherwise `now_ns()` drifts silently. The TSC itself and a
+    /// usable frequency leaf are still required
+    pub fn new_allow_variable() -> Result<Self, Error> {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        let (freq, source) = Self::detect_freq(false)?;
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+        let (freq, source) = (Self::cpu_freq()?, FreqSource::Platform);
+        Ok(Self::from_detected(freq, source))
+    }
+
+    /// Requires a TSC with the invariant bit, then tries each frequency source in order: the
+    /// native CPUID leaves, 0x15 (crystal clock ratio) and then 0x16 (nominal base frequency);
+    /// inside a VM, the hypervisor's timing leaf 0x40000010; then the OS: on Linux the kernel's
+    /// own calibration (see `freq_from_kernel()`), on Windows (with the `windows` feature) timing
+    /// against QueryPerformanceCounter. The result must lie within `PLAUSIBLE_FREQ`
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
     pub fn cpu_freq() -> Result<u64, Error> {
-        use core::arch::x86_64::__cpuid;
+        Self::detect_freq(true).map(|(freq, _)| freq)
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn detect_freq(require_invariant: bool) -> Result<(u64, FreqSource), Error> {
+        let (freq, source) = Self::reported_freq(require_invariant)?;
+        Ok((Self::plausible_freq(freq)?, source))
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn reported_freq(require_invariant: bool) -> Result<(u64, FreqSource), Error> {
+        match Self::cpuid_freq(require_invariant) {
+            #[cfg(target_os = "linux")]
+            Err(
+                err @ (Error::CpuidLeafTscFailed
+                | Error::CpuidLeafFreqFailed
+                | Error::HypervisorLeafFailed),
+            ) => Self::freq_from_kernel()
+                .map(|freq| (freq, FreqSource::Kernel))
+                .ok_or(err),
+            #[cfg(all(windows, feature = "windows"))]
+            Err(
+                Error::CpuidLeafTscFailed
+                | Error::CpuidLeafFreqFailed
+                | Error::HypervisorLeafFailed,
+            ) => Self::qpc_freq().map(|freq| (freq, FreqSource::Calibrated)),
+            res => res,
+        }
+    }
+
+    // the frequency from `source` alone, for `TscBuilder::frequency_source()`; calibration is
+    // left to the builder
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn freq_from_source(source: FreqSource, require_invariant: bool) -> Result<u64, Error> {
+        if !Self::has_tsc() {
+            return Err(Error::TscNotSupported);
+        }
+        if require_invariant && !Self::has_invariant_tsc() {
+            return Err(Error::InvariantTscNotSupported);
+        }
+        let freq = match source {
+            FreqSource::Cpuid15 => match Self::native_leaf_freq()? {
+                (freq, FreqSource::Cpuid15) => freq,
+                _ => return Err(Error::CpuidLeafTscFailed),
+            },
+            FreqSource::Cpuid16 => {
+                // see `native_leaf_freq()` for the max leaf check and subleaf
+                let mhz = match arch::__cpuid(0x0).eax {
+                    0x16.. => arch::__cpuid_count(0x16, 0).eax,
+                    _ => 0,
+                };
+                if mhz == 0 {
+                    return Err(Error::CpuidLeafFreqFailed);
+                }
+                mhz as u64 * 1_000_000
+            }
+            FreqSource::Hypervisor => Self::hypervisor_freq()?,
+            #[cfg(target_os = "linux")]
+            FreqSource::Kernel => {
+                Self::freq_from_kernel().ok_or(Error::SourceUnavailable(source))?
+            }
+            _ => return Err(Error::SourceUnavailable(source)),
+        };
+        Self::plausible_freq(freq)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    fn freq_from_source(source: FreqSource, _require_invariant: bool) -> Result<u64, Error> {
+        match source {
+            FreqSource::Platform => Self::cpu_freq(),
+            _ => Err(Error::SourceUnavailable(source)),
+        }
+    }
 
-        let res = unsafe { __cpuid(0x1) };
-        if res.edx & (1 << 4) == 0 {
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn cpuid_freq(require_invariant: bool) -> Result<(u64, FreqSource), Error> {
+        if !Self::has_tsc() {
             return Err(Error::TscNotSupported);
         }
-        let res = unsafe { __cpuid(0x80000007) };
-        if res.edx & (1 << 8) == 0 {
+        if require_invariant && !Self::has_invariant_tsc() {
             return Err(Error::InvariantTscNotSupported);
         }
-        let res = unsafe { __cpuid(0x15) };
+        Self::leaf_freq()
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub fn capabilities() -> TscCaps {
+        #[cfg(all(target_os = "linux", feature = "std"))]
+        let flags = std::fs::read_to_string("/proc/cpuinfo")
+            .ok()
+            .and_then(|cpuinfo| Self::parse_tsc_flags(&cpuinfo));
+        #[cfg(not(all(target_os = "linux", feature = "std")))]
+        let flags: Option<(bool, bool)> = None;
+
+        TscCaps {
+            has_tsc: Self::has_tsc(),
+            has_invariant_tsc: Self::has_invariant_tsc(),
+            has_constant_tsc: flags.map(|(constant, _)| constant),
+            has_nonstop_tsc: flags.map(|(_, nonstop)| nonstop),
+            has_rdtscp: Self::has_rdtscp(),
+            freq: Self::leaf_freq().ok().map(|(freq, _)| freq),
+        }
+    }
+
+    // the generic timer (and the RISC-V `time` CSR) always runs at a fixed rate
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    pub fn capabilities() -> TscCaps {
+        TscCaps {
+            has_tsc: true,
+            has_invariant_tsc: true,
+            has_constant_tsc: Some(true),
+            has_nonstop_tsc: Some(true),
+            has_rdtscp: Self::has_rdtscp(),
+            freq: Self::cpu_freq().ok(),
+        }
+    }
+
+    // the `constant_tsc` and `nonstop_tsc` flags of the first processor in /proc/cpuinfo
+    #[cfg(all(
+        target_os = "linux",
+        feature = "std",
+        any(target_arch = "x86_64", target_arch = "x86")
+    ))]
+    fn parse_tsc_flags(cpuinfo: &str) -> Option<(bool, bool)> {
+        let line = cpuinfo.lines().find(|line| line.starts_with("flags"))?;
+        let (_, flags) = line.split_once(':')?;
+        let has = |flag| flags.split_whitespace().any(|f| f == flag);
+        Some((has("constant_tsc"), has("nonstop_tsc")))
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub fn cpuid_debug() -> CpuidReport {
+        use arch::{__cpuid, __cpuid_count};
+
+        let leaf_15 = __cpuid_count(0x15, 0);
+        CpuidReport {
+            leaf_1_edx: __cpuid(0x1).edx,
+            leaf_80000007_edx: __cpuid(0x80000007).edx,
+            leaf_15_eax: leaf_15.eax,
+            leaf_15_ebx: leaf_15.ebx,
+            leaf_15_ecx: leaf_15.ecx,
+            leaf_16_eax: __cpuid_count(0x16, 0).eax,
+        }
+    }
+
+    // probed once and cached, since `read_tsc_checked()` tests it on every read
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn has_tsc() -> bool {
+        use core::sync::atomic::AtomicU8;
+        static TSC: AtomicU8 = AtomicU8::new(CACHE_UNKNOWN);
+        cached_cpu_flag(&TSC, || arch::__cpuid(0x1).edx & (1 << 4) != 0)
+    }
+
+    // edx of an extended leaf, or 0 (no feature bits) if it's above the max extended leaf
+    // (0x80000000 eax): like a basic leaf above the max, it would read another leaf's registers
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn extended_leaf_edx(leaf: u32) -> u32 {
+        use arch::__cpuid;
+        if __cpuid(0x80000000).eax < leaf {
+            return 0;
+        }
+        __cpuid(leaf).edx
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn has_invariant_tsc() -> bool {
+        Self::extended_leaf_edx(0x80000007) & (1 << 8) != 0
+    }
+
+    /// Whether the CPU supports `rdtscp` (executing it otherwise faults). Probed once and cached
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub fn has_rdtscp() -> bool {
+        use core::sync::atomic::AtomicU8;
+        static RDTSCP: AtomicU8 = AtomicU8::new(CACHE_UNKNOWN);
+        cached_cpu_flag(&RDTSCP, || {
+            Self::extended_leaf_edx(0x80000001) & (1 << 27) != 0
+        })
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    pub fn has_rdtscp() -> bool {
+        false
+    }
+
+    /// Whether the local APIC timer supports TSC-deadline mode (CPUID 0x1 ecx bit 24), where it
+    /// fires when the TSC reaches the value written to `IA32_TSC_DEADLINE`. Only the kernel can
+    /// program the MSR; this just reports whether the mode exists
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub fn has_tsc_deadline() -> bool {
+        arch::__cpuid(0x1).ecx & (1 << 24) != 0
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    pub fn has_tsc_deadline() -> bool {
+        false
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn leaf_freq() -> Result<(u64, FreqSource), Error> {
+        Self::native_leaf_freq().or_else(|err| {
+            if Self::is_hypervisor() {
+                Self::hypervisor_freq().map(|freq| (freq, FreqSource::Hypervisor))
+            } else {
+                Err(err)
+            }
+        })
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn is_hypervisor() -> bool {
+        arch::__cpuid(0x1).ecx & (1 << 31) != 0
+    }
+
+    // KVM, VMware and Hyper-V guests commonly expose the TSC frequency in kHz through the
+    // "generic timing" leaf 0x40000010, when the hypervisor's max leaf (0x40000000 eax) reaches it
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn hypervisor_freq() -> Result<u64, Error> {
+        use arch::__cpuid;
+
+        if __cpuid(0x40000000).eax < 0x40000010 {
+            return Err(Error::HypervisorLeafFailed);
+        }
+        let res = __cpuid(0x40000010);
+        if res.eax == 0 {
+            return Err(Error::HypervisorLeafFailed);
+        }
+        Ok(res.eax as u64 * 1000 /* KHZ */)
+    }
+
+    // leaves 0x15 and 0x16 have no subleaves, but CPUID still takes ecx as the subleaf index for
+    // every leaf, and passing an explicit 0 guarantees the architectural subleaf instead of
+    // whatever ecx happened to hold. A leaf above the max basic leaf (0x0 eax) returns the
+    // registers of the max leaf on Intel, so it must be checked first
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn native_leaf_freq() -> Result<(u64, FreqSource), Error> {
+        use arch::{__cpuid, __cpuid_count};
+
+        let max_leaf = __cpuid(0x0).eax;
+        if max_leaf < 0x15 {
+            return Err(Error::CpuidLeafTscFailed);
+        }
+        let res = __cpuid_count(0x15, 0);
         if res.ebx == 0 || res.eax == 0 {
             return Err(Error::CpuidLeafTscFailed);
         }
 
-        let freq = if res.ecx != 0 {
-            (res.ecx as u64 * res.ebx as u64) / (res.eax as u64)
-        } else {
-            let res = unsafe { __cpuid(0x16) };
-            if res.eax == 0 {
-                return Err(Error::CpuidLeafFreqFailed);
-            }
-            res.eax as u64 * 1_000_000 /* MHZ */
+        if res.ecx != 0 {
+            let freq = (res.ecx as u64 * res.ebx as u64) / (res.eax as u64);
+            return Ok((freq, FreqSource::Cpuid15));
+        }
+        if max_leaf < 0x16 {
+            return Err(Error::CpuidLeafFreqFailed);
+        }
+        let res = __cpuid_count(0x16, 0);
+        if res.eax == 0 {
+            return Err(Error::CpuidLeafFreqFailed);
+        }
+        Ok((
+            res.eax as u64 * 1_000_000, /* MHZ */
+            FreqSource::Cpuid16,
+        ))
+    }
+
+    /// CPUID leaf 0x16's processor base, maximum (turbo) and bus (reference) frequencies, in
+    /// MHz, or `None` if the CPU doesn't report the leaf. For display only: the TSC runs at the
+    /// base rate at most, but frequency detection prefers leaf 0x15's crystal-derived one (see
+    /// `cpu_freq()`). A CPU may leave the maximum or bus frequency 0
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub fn frequencies_mhz() -> Option<(u16, u16, u16)> {
+        use arch::{__cpuid, __cpuid_count};
+
+        if __cpuid(0x0).eax < 0x16 {
+            return None;
+        }
+        // each register holds its frequency in bits 15:0, the rest is reserved
+        let res = __cpuid_count(0x16, 0);
+        let base = res.eax as u16;
+        (base != 0).then_some((base, res.ebx as u16, res.ecx as u16))
+    }
+
+    /// CPUID leaf 0x16's frequencies; always `None` on non-x86 targets
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    pub fn frequencies_mhz() -> Option<(u16, u16, u16)> {
+        None
+    }
+
+    /// The TSC frequency as calibrated by the Linux kernel, read from the TSC-to-ns conversion
+    /// factors it publishes to userspace in the metadata page of a perf event. `None` if perf
+    /// events are unavailable (e.g. disallowed by `perf_event_paranoid` or seccomp) or the kernel
+    /// doesn't use the TSC as its clock
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+    pub fn freq_from_kernel() -> Option<u64> {
+        perf::perf_tsc_freq()
+    }
+
+    // `QueryPerformanceFrequency` reports the rate of QPC, which modern Windows usually fixes at
+    // 10 MHz rather than exposing the TSC rate, so the TSC is timed against QPC over a short window
+    #[cfg(all(
+        windows,
+        feature = "windows",
+        any(target_arch = "x86_64", target_arch = "x86")
+    ))]
+    fn qpc_freq() -> Result<u64, Error> {
+        use windows_sys::Win32::System::Performance::{
+            QueryPerformanceCounter, QueryPerformanceFrequency,
         };
+        const WINDOW_MS: i64 = 10;
+
+        let mut qpf = 0;
+        if unsafe { QueryPerformanceFrequency(&mut qpf) } == 0 || qpf <= 0 {
+            return Err(Error::QueryPerformanceFrequencyFailed);
+        }
+        let (mut q0, mut q1) = (0, 0);
+        unsafe { QueryPerformanceCounter(&mut q0) };
+        let c0 = Self::read_tsc();
+        let deadline = q0 + qpf * WINDOW_MS / 1000;
+        while q1 < deadline {
+            core::hint::spin_loop();
+            unsafe { QueryPerformanceCounter(&mut q1) };
+        }
+        let c1 = Self::read_tsc();
+
+        let freq = (c1.saturating_sub(c0) as u128 * qpf as u128 / (q1 - q0) as u128) as u64;
+        if freq == 0 {
+            return Err(Error::QueryPerformanceFrequencyFailed);
+        }
         Ok(freq)
     }
 
-    #[cfg(target_arch = "aarch64")]
+    /// Reads `cntfrq_el0`. Firmware is responsible for programming it and some boards leave it
+    /// at 0 or garbage, which is reported as `Error::ImplausibleFrequency` instead of producing
+    /// nonsense timestamps; `new_with_source()` and `global()` then fall back to `calibrate()`
+    #[cfg(all(target_arch = "aarch64", not(target_os = "macos")))]
     pub fn cpu_freq() -> Result<u64, Error> {
-        use std::arch::asm;
+        use core::arch::asm;
         let freq: u64;
         unsafe {
             asm!("mrs {}, cntfrq_el0", out(reg) freq);
         }
-        Ok(freq)
+        Self::plausible_freq(freq)
+    }
+
+    // userspace access to the generic timer is restricted under macOS, where `mach_absolute_time()`
+    // is the sanctioned counter; its timebase gives the tick length as a numer/denom ns ratio
+    #[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+    pub fn cpu_freq() -> Result<u64, Error> {
+        let (numer, denom) = mach::timebase().ok_or(Error::TimebaseFreqUnavailable)?;
+        // rounded to nearest, like `from_ns_per_tick()`
+        Self::plausible_freq((1_000_000_000 * denom as u64 + numer as u64 / 2) / numer as u64)
+    }
+
+    // the `time` CSR ticks at the platform's timebase frequency, which is only published through
+    // the device tree
+    #[cfg(all(target_arch = "riscv64", feature = "std"))]
+    pub fn cpu_freq() -> Result<u64, Error> {
+        let cell = std::fs::read("/proc/device-tree/cpus/timebase-frequency")
+            .map_err(|_| Error::TimebaseFreqUnavailable)?;
+        let freq = match *cell.as_slice() {
+            [a, b, c, d] => u32::from_be_bytes([a, b, c, d]) as u64,
+            [a, b, c, d, e, f, g, h] => u64::from_be_bytes([a, b, c, d, e, f, g, h]),
+            _ => return Err(Error::TimebaseFreqUnavailable),
+        };
+        if freq == 0 {
+            return Err(Error::TimebaseFreqUnavailable);
+        }
+        Self::plausible_freq(freq)
+    }
+
+    #[cfg(all(target_arch = "riscv64", not(feature = "std")))]
+    pub fn cpu_freq() -> Result<u64, Error> {
+        Err(Error::TimebaseFreqUnavailable)
+    }
+
+    // `read_tsc()` already counts nanoseconds under WASM
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    pub fn cpu_freq() -> Result<u64, Error> {
+        Ok(1_000_000_000)
+    }
+
+    // the `fallback` `read_tsc()` counts nanoseconds too
+    #[cfg(all(
+        feature = "fallback",
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "x86",
+            target_arch = "aarch64",
+            target_arch = "riscv64",
+            all(target_arch = "wasm32", feature = "wasm")
+        ))
+    ))]
+    pub fn cpu_freq() -> Result<u64, Error> {
+        Ok(1_000_000_000)
+    }
+
+    /// The band of frequencies `cpu_freq()` accepts; anything outside it is reported as
+    /// `Error::ImplausibleFrequency` rather than silently producing nonsense timestamps
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub const PLAUSIBLE_FREQ: core::ops::RangeInclusive<u64> = 100_000_000..=10_000_000_000;
+
+    // the generic timer and the RISC-V timebase legitimately tick at a few MHz (e.g. 24 MHz)
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    pub const PLAUSIBLE_FREQ: core::ops::RangeInclusive<u64> = 1_000_000..=10_000_000_000;
+
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "x86",
+            target_arch = "aarch64",
+            all(target_arch = "riscv64", feature = "std")
+        )),
+        allow(dead_code)
+    )]
+    fn plausible_freq(freq: u64) -> Result<u64, Error> {
+        if Self::PLAUSIBLE_FREQ.contains(&freq) {
+            Ok(freq)
+        } else {
+            Err(Error::ImplausibleFrequency(freq))
+        }
     }
 
+    /// The fence `read_tsc()` compiles to, and the one clocks read with unless
+    /// `TscBuilder::default_fence()` picks another: chosen by the `fence-*` features, `Lfence` if
+    /// none is set
+    #[cfg(not(any(feature = "fence-rdtscp", feature = "fence-none")))]
+    pub const DEFAULT_FENCE: Fence = Fence::Lfence;
+    #[cfg(feature = "fence-rdtscp")]
+    pub const DEFAULT_FENCE: Fence = Fence::Rdtscp;
+    #[cfg(feature = "fence-none")]
+    pub const DEFAULT_FENCE: Fence = Fence::None;
+
+    // the fence is picked at compile time by the `fence-*` features, `lfence` if none is set
     #[inline(always)]
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        not(any(feature = "fence-rdtscp", feature = "fence-none"))
+    ))]
+    #[must_use]
     pub fn read_tsc() -> u64 {
-        use core::arch::x86_64::{_mm_lfence, _rdtsc};
+        use arch::{_mm_lfence, _rdtsc};
         unsafe {
             _mm_lfence();
             _rdtsc()
@@ -74,9 +1096,31 @@ impl TSC {
     }
 
     #[inline(always)]
-    #[cfg(target_arch = "aarch64")]
+    #[cfg(all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        feature = "fence-rdtscp"
+    ))]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        let mut aux = 0;
+        unsafe { arch::__rdtscp(&mut aux) }
+    }
+
+    #[inline(always)]
+    #[cfg(all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        feature = "fence-none"
+    ))]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        unsafe { arch::_rdtsc() }
+    }
+
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", not(target_os = "macos")))]
+    #[must_use]
     pub fn read_tsc() -> u64 {
-        use std::arch::asm;
+        use core::arch::asm;
         let value: u64;
         unsafe {
             asm!("mrs {}, cntvct_el0", out(reg) value);
@@ -84,38 +1128,776 @@ impl TSC {
         value
     }
 
-    pub fn get_freq(&self) -> u64 {
-        self.freq
+    /// Reads the physical counter `cntpct_el0`. `read_tsc()` reads the virtual counter
+    /// `cntvct_el0`, which is the physical one minus the offset in `cntvoff_el2`: the two are
+    /// equal on bare metal and under most hosts, but differ inside guests whose hypervisor sets a
+    /// nonzero offset. Both tick at the `cntfrq_el0` frequency. The kernel must grant EL0 access
+    /// to the physical counter, otherwise the read traps
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", not(target_os = "macos")))]
+    #[must_use]
+    pub fn read_tsc_physical() -> u64 {
+        use core::arch::asm;
+        let value: u64;
+        unsafe {
+            asm!("mrs {}, cntpct_el0", out(reg) value);
+        }
+        value
     }
 
-    pub fn now_f64(&self) -> f64 {
-        Self::read_tsc() as f64 / self.freq as f64
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", target_os = "macos"))]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        mach::absolute_time()
     }
 
-    #[inline(never)]
-    pub fn now_ns(&self) -> u64 {
-        let tsc = Self::read_tsc();
-        let (secs, rem) = (tsc / self.freq, tsc % self.freq);
-        secs * 1_000_000_000 + (rem * 1_000_000_000 / self.freq)
+    #[inline(always)]
+    #[cfg(target_arch = "riscv64")]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        use core::arch::asm;
+        let value: u64;
+        unsafe {
+            asm!("rdtime {}", out(reg) value);
+        }
+        value
     }
-}
 
-#[test]
-fn test_perf() {
-    use std::time::{Duration, Instant};
-    let t = TSC::new().unwrap();
+    // there's no cycle counter under WASM, so this is `performance.now()` (in ms, as an f64)
+    // scaled to nanoseconds; browsers coarsen it to anywhere from 5us to 1ms. `performance` is
+    // looked up on the global object rather than `window`, which workers don't have, and a
+    // runtime without one panics instead of reading 0 forever
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        use wasm_bindgen::{JsCast, JsValue};
+        std::thread_local! {
+            static PERFORMANCE: web_sys::Performance =
+                js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+                    .ok()
+                    .and_then(|performance| performance.dyn_into().ok())
+                    .expect("tsc: the JavaScript global scope has no `performance`");
+        }
+        let ms = PERFORMANCE.with(|performance| performance.now());
+        (ms * 1_000_000.0) as u64
+    }
 
-    let t0 = Instant::now();
-    let mut counter = 0;
-    for _ in 0..100_000 {
-        let tmp = Instant::now();
-        counter += tmp.duration_since(t0).as_nanos();
+    // without a counter to read, this is the nanoseconds `Instant` has measured since the first
+    // call
+    #[cfg(all(
+        feature = "fallback",
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "x86",
+            target_arch = "aarch64",
+            target_arch = "riscv64",
+            all(target_arch = "wasm32", feature = "wasm")
+        ))
+    ))]
+    #[must_use]
+    pub fn read_tsc() -> u64 {
+        static BASE: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+        BASE.get_or_init(Instant::now).elapsed().as_nanos() as u64
     }
-    let t1 = Instant::now();
-    assert!(counter > 10);
-    println!("{:?}", t1.duration_since(t0));
 
-    let t0 = Instant::now();
+    /// Like `read_tsc()`, but first verifies (once, then from a cache) that the CPU has a TSC,
+    /// since executing `rdtsc` on one that doesn't faults. Only x86 can lack the counter. With
+    /// `fence-rdtscp`, where `read_tsc()` is a bare `rdtscp`, it also fails with
+    /// `Error::RdtscpNotSupported` on CPUs without that instruction
+    #[inline(always)]
+    #[must_use = "this returns the reading rather than storing it anywhere"]
+    pub fn read_tsc_checked() -> Result<u64, Error> {
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        if !Self::has_tsc() {
+            return Err(Error::TscNotSupported);
+        }
+        #[cfg(all(
+            any(target_arch = "x86_64", target_arch = "x86"),
+            feature = "fence-rdtscp"
+        ))]
+        if !Self::has_rdtscp() {
+            return Err(Error::RdtscpNotSupported);
+        }
+        Ok(Self::read_tsc())
+    }
+
+    /// Reads the TSC fenced on both sides (`lfence; rdtsc; lfence`): the read waits for earlier
+    /// instructions, and later ones can't start before it. `read_tsc()` only fences before the
+    /// read, so an end-of-region read may be overlapped by the code after it; use this for *both*
+    /// endpoints of a measurement so they are serialized symmetrically
+    #[inline(always)]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    #[must_use]
+    pub fn read_tsc_bracketed() -> u64 {
+        use arch::{_mm_lfence, _rdtsc};
+        unsafe {
+            _mm_lfence();
+            let tsc = _rdtsc();
+            _mm_lfence();
+            tsc
+        }
+    }
+
+    // `isb` is the aarch64 counterpart of `lfence` for ordering counter reads
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", not(target_os = "macos")))]
+    #[must_use]
+    pub fn read_tsc_bracketed() -> u64 {
+        use core::arch::asm;
+        let value: u64;
+        unsafe {
+            asm!("isb", "mrs {}, cntvct_el0", "isb", out(reg) value);
+        }
+        value
+    }
+
+    #[inline(always)]
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        all(target_arch = "aarch64", not(target_os = "macos"))
+    )))]
+    #[must_use]
+    pub fn read_tsc_bracketed() -> u64 {
+        Self::read_tsc()
+    }
+
+    /// Two back-to-back `read_tsc_bracketed()` reads. Nothing runs between them, so their
+    /// difference is the cost of one serialized read at this instant: use the second as the
+    /// timestamp and the difference as its uncertainty, in cycles
+    #[inline(always)]
+    #[must_use]
+    pub fn read_pair() -> (u64, u64) {
+        let first = Self::read_tsc_bracketed();
+        let second = Self::read_tsc_bracketed();
+        (first, second)
+    }
+
+    /// Reads the TSC without any fence. The read is not serialized, so the CPU may reorder it with
+    /// the surrounding instructions; cheaper than `read_tsc()` for coarse timestamping in hot loops
+    #[inline(always)]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    #[must_use]
+    pub fn read_tsc_raw() -> u64 {
+        use arch::_rdtsc;
+        unsafe { _rdtsc() }
+    }
+
+    #[inline(always)]
+    #[cfg(all(target_arch = "aarch64", not(target_os = "macos")))]
+    #[must_use]
+    pub fn read_tsc_raw() -> u64 {
+        use core::arch::asm;
+        let value: u64;
+        unsafe {
+            asm!("mrs {}, cntvct_el0", out(reg) value);
+        }
+        value
+    }
+
+    #[inline(always)]
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        all(target_arch = "aarch64", not(target_os = "macos"))
+    )))]
+    #[must_use]
+    pub fn read_tsc_raw() -> u64 {
+        Self::read_tsc()
+    }
+
+    /// Reads the TSC along with `IA32_TSC_AUX`, which Linux populates with the id of the core
+    /// (low 12 bits) and NUMA node (the bits above) the read executed on. Falls back to
+    /// `(read_tsc(), 0)` when `rdtscp` isn't supported (see `has_rdtscp()`)
+    #[inline(always)]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    #[must_use]
+    pub fn read_tscp() -> (u64, u32) {
+        use arch::__rdtscp;
+        if !Self::has_rdtscp() {
+            return (Self::read_tsc_with(Fence::Lfence), 0);
+        }
+        let mut aux = 0;
+        let tsc = unsafe { __rdtscp(&mut aux) };
+        (tsc, aux)
+    }
+
+    #[inline(always)]
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    #[must_use]
+    pub fn read_tscp() -> (u64, u32) {
+        (Self::read_tsc(), 0)
+    }
+
+    /// Reads the TSC with an explicit ordering strategy, from cheapest to strongest:
+    /// * `None`: plain `rdtsc`; may be reordered with both earlier and later instructions
+    /// * `Lfence` (`read_tsc()` by default): `lfence; rdtsc` waits for all earlier instructions to
+    ///   complete locally, but earlier stores may still be in flight and later instructions may
+    ///   start before the read
+    /// * `MfenceLfence`: `mfence; lfence; rdtsc` additionally drains earlier loads and stores, for
+    ///   when the timestamp must follow memory operations; the most expensive
+    /// * `Rdtscp`: waits for earlier instructions (like `Lfence`) and costs about the same, but
+    ///   later instructions may still start before the read. Falls back to `Lfence` without
+    ///   `rdtscp` support
+    ///
+    /// On non-x86 targets every strategy is the same as `read_tsc()`
+    #[inline(always)]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    #[must_use]
+    pub fn read_tsc_with(fence: Fence) -> u64 {
+        use arch::{_mm_lfence, _mm_mfence, _rdtsc};
+        match fence {
+            Fence::None => Self::read_tsc_raw(),
+            Fence::Lfence => unsafe {
+                _mm_lfence();
+                _rdtsc()
+            },
+            Fence::MfenceLfence => unsafe {
+                _mm_mfence();
+                _mm_lfence();
+                _rdtsc()
+            },
+            Fence::Rdtscp => Self::read_tscp().0,
+        }
+    }
+
+    #[inline(always)]
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    #[must_use]
+    pub fn read_tsc_with(_fence: Fence) -> u64 {
+        Self::read_tsc()
+    }
+
+    /// The frequency in Hz; rounded to the nearest Hz for clocks whose tick isn't a whole
+    /// fraction of a second (see `from_ns_per_tick()`)
+    pub const fn get_freq(&self) -> u64 {
+        self.freq
+    }
+
+    /// The exact tick length, `(numer, denom)` nanoseconds in lowest terms
+    pub const fn ns_per_tick(&self) -> (u64, u64) {
+        (self.tick_numer, self.tick_denom)
+    }
+
+    // the unrounded frequency. `1e9 / tick_numer` is exact for clocks built from a frequency
+    // (the numerator divides 1e9), so then this is exactly `freq as f64`
+    pub(crate) fn freq_f64(&self) -> f64 {
+        self.tick_denom as f64 * (1_000_000_000.0 / self.tick_numer as f64)
+    }
+
+    /// Where the frequency came from, or `None` if it was given (`from_freq()`, `try_from()`, a
+    /// deserialized clock)
+    pub fn freq_source(&self) -> Option<FreqSource> {
+        self.source
+    }
+
+    /// The estimated 1-sigma uncertainty of the frequency in parts per million: the spread
+    /// across the rounds of `calibrate_refined()`. `None` for reported frequencies (the crate
+    /// can't know the error of a CPUID or kernel value) and single-round calibrations
+    pub fn freq_uncertainty_ppm(&self) -> Option<f64> {
+        self.uncertainty_ppm
+    }
+
+    /// Whether the frequency is CPUID leaf 0x16's nominal base frequency. That leaf reports whole
+    /// MHz, rounded from the real rate (often by far more than the crystal error of leaf 0x15),
+    /// so such clocks are worth refining with `calibrate_refined()` when drift matters
+    pub fn is_nominal(&self) -> bool {
+        self.source == Some(FreqSource::Cpuid16)
+    }
+
+    pub fn freq_mhz(&self) -> f64 {
+        self.freq_f64() / 1_000_000.0
+    }
+
+    pub fn freq_ghz(&self) -> f64 {
+        self.freq_f64() / 1_000_000_000.0
+    }
+
+    /// The frequency with two decimals in the largest unit below it, e.g. "2.40 GHz" or
+    /// "24.00 MHz"
+    #[cfg(feature = "std")]
+    pub fn freq_human(&self) -> String {
+        match self.freq {
+            1_000_000_000.. => format!("{:.2} GHz", self.freq_ghz()),
+            1_000_000.. => format!("{:.2} MHz", self.freq_mhz()),
+            1_000.. => format!("{:.2} kHz", self.freq_f64() / 1_000.0),
+            freq => format!("{freq} Hz"),
+        }
+    }
+
+    /// The fence the instance methods (`now_ns()`, `start()`, ...) read the counter with;
+    /// `DEFAULT_FENCE` (like `read_tsc()`) unless chosen through `TscBuilder::default_fence()`
+    pub const fn default_fence(&self) -> Fence {
+        self.fence
+    }
+
+    #[inline(always)]
+    fn read(&self) -> u64 {
+        Self::read_tsc_with(self.fence) & self.counter_mask()
+    }
+
+    /// The number of significant bits in the counter, after which it wraps to 0. The TSC and the
+    /// other supported counters are architecturally 64 bits wide, and no CPUID leaf reports a
+    /// narrower one, so this is 64 unless set with `TscBuilder::counter_bits()` (e.g. for a
+    /// hypervisor that emulates a 56-bit counter)
+    pub fn counter_bits(&self) -> u32 {
+        self.counter_bits
+    }
+
+    #[inline(always)]
+    fn counter_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.counter_bits)
+    }
+
+    // `end - start` in cycles, or `None` if `end` is behind `start`. A 64-bit counter never wraps
+    // in practice, but a narrow one can within the machine's uptime, so a numerically smaller
+    // `end` counts as a wrap (not a jump backwards) if it's less than half the range ahead
+    #[inline(always)]
+    fn cycles_between(&self, start: u64, end: u64) -> Option<u64> {
+        if self.counter_bits == 64 {
+            return end.checked_sub(start);
+        }
+        let mask = self.counter_mask();
+        let delta = end.wrapping_sub(start) & mask;
+        (delta <= mask / 2).then_some(delta)
+    }
+
+    pub fn cycles_per_ns(&self) -> f64 {
+        self.tick_denom as f64 / self.tick_numer as f64
+    }
+
+    pub fn ns_per_cycle(&self) -> f64 {
+        self.tick_numer as f64 / self.tick_denom as f64
+    }
+
+    /// The granularity of timestamps in nanoseconds, i.e. the length of one tick: well below 1ns
+    /// for an x86 TSC, but ~41.7ns for the 24 MHz generic timer common on aarch64
+    pub fn resolution_ns(&self) -> f64 {
+        self.ns_per_cycle()
+    }
+
+    #[must_use]
+    pub fn now_f64(&self) -> f64 {
+        self.read() as f64 / self.freq_f64()
+    }
+
+    /// Seconds since the clock was constructed. Unlike `now_f64()`, which counts from when the
+    /// counter started (usually boot), small values keep the full precision of the f64 for the
+    /// fractional part
+    #[must_use]
+    pub fn now_f64_since_start(&self) -> f64 {
+        (self.read().wrapping_sub(self.start) & self.counter_mask()) as f64 / self.freq_f64()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn now_ns(&self) -> u64 {
+        self.tsc_to_ns(self.read())
+    }
+
+    /// `now_ns()` kept out of line. Inlined reads can be reordered or merged with the code
+    /// around them at each call site, so timing the clock itself (or comparing it against other
+    /// clocks) is steadier with a single, always-called copy
+    #[inline(never)]
+    #[must_use]
+    pub fn now_ns_noinline(&self) -> u64 {
+        self.now_ns()
+    }
+
+    #[must_use]
+    pub fn now_micros(&self) -> u64 {
+        self.tsc_to_micros(self.read())
+    }
+
+    // straight from the tick ratio, so there's no nanosecond value to round or overflow first;
+    // truncated to u64 like `tsc_to_ns()`
+    const fn tsc_to_micros(&self, raw: u64) -> u64 {
+        (raw as u128 * self.tick_numer as u128 / (self.tick_denom as u128 * 1_000)) as u64
+    }
+
+    #[must_use]
+    pub fn now_secs(&self) -> u64 {
+        self.split_secs(self.read()).0
+    }
+
+    /// Captures a starting point for `elapsed_ns()`/`elapsed_duration()`; the same as `read_tsc()`
+    #[inline(always)]
+    #[must_use]
+    pub fn start(&self) -> u64 {
+        self.read()
+    }
+
+    /// Nanoseconds since `start_tsc`, saturating to 0 if the counter appears to have gone backwards
+    pub fn elapsed_ns(&self, start_tsc: u64) -> u64 {
+        self.delta_ns_saturating(start_tsc, self.read())
+    }
+
+    /// Nanoseconds from `start` to `end`, or `None` if `end` is earlier, i.e. the counter jumped
+    /// backwards (typically because the reads ran on different cores). Counters narrower than
+    /// 64 bits (see `counter_bits()`) are allowed to wrap in between
+    pub fn delta_ns(&self, start: u64, end: u64) -> Option<u64> {
+        self.cycles_between(start, end)
+            .map(|delta| self.tsc_to_ns(delta))
+    }
+
+    /// Like `delta_ns()`, but returns 0 if `end` is earlier than `start`
+    pub fn delta_ns_saturating(&self, start: u64, end: u64) -> u64 {
+        self.tsc_to_ns(self.cycles_between(start, end).unwrap_or(0))
+    }
+
+    /// Runs `f` between two serialized TSC reads, returning its result and the elapsed cycles.
+    /// The result goes through `black_box` so the work can't be optimized out of the region.
+    /// Both ends use `read_tsc_bracketed()`, so neither read overlaps the surrounding code
+    #[inline(always)]
+    pub fn measure<R>(f: impl FnOnce() -> R) -> (R, u64) {
+        let start = Self::read_tsc_bracketed();
+        let res = core::hint::black_box(f());
+        let end = Self::read_tsc_bracketed();
+        (res, end.saturating_sub(start))
+    }
+
+    /// Like `measure()`, but returns the elapsed time in nanoseconds
+    #[inline(always)]
+    pub fn measure_ns<R>(&self, f: impl FnOnce() -> R) -> (R, u64) {
+        let (res, cycles) = Self::measure(f);
+        (res, self.tsc_to_ns(cycles))
+    }
+
+    /// The median cost, in cycles, of a back-to-back pair of `read_tsc_bracketed()` calls; this
+    /// is the bias a `measure()` of an empty closure would report. Measured on first call and
+    /// cached
+    pub fn estimate_overhead() -> u64 {
+        use core::sync::atomic::{AtomicU64, Ordering};
+        const SAMPLES: usize = 255;
+        static OVERHEAD: AtomicU64 = AtomicU64::new(u64::MAX);
+
+        let cached = OVERHEAD.load(Ordering::Relaxed);
+        if cached != u64::MAX {
+            return cached;
+        }
+        let mut deltas = [0u64; SAMPLES];
+        for delta in deltas.iter_mut() {
+            let start = Self::read_tsc_bracketed();
+            *delta = Self::read_tsc_bracketed().saturating_sub(start);
+        }
+        deltas.sort_unstable();
+        let overhead = deltas[SAMPLES / 2];
+        OVERHEAD.store(overhead, Ordering::Relaxed);
+        overhead
+    }
+
+    /// Like `measure()`, but subtracts `estimate_overhead()` from the elapsed cycles (saturating
+    /// at 0), which makes very short measurements more accurate
+    #[inline(always)]
+    pub fn measure_compensated<R>(f: impl FnOnce() -> R) -> (R, u64) {
+        let overhead = Self::estimate_overhead();
+        let (res, cycles) = Self::measure(f);
+        (res, cycles.saturating_sub(overhead))
+    }
+
+    #[inline(always)]
+    pub fn measure_compensated_ns<R>(&self, f: impl FnOnce() -> R) -> (R, u64) {
+        let (res, cycles) = Self::measure_compensated(f);
+        (res, self.tsc_to_ns(cycles))
+    }
+
+    /// The current time as `(seconds, nanoseconds)`, computed with integer math only so results
+    /// are bit-exact across builds; the same nanoseconds as `now_ns()`, split at the second
+    #[must_use]
+    pub fn now_fixed(&self) -> (u64, u32) {
+        self.tsc_to_fixed(self.read())
+    }
+
+    /// Converts a raw TSC value to `(seconds, nanoseconds)` with the same multiply-shift as
+    /// `tsc_to_ns()`, carried in u128 so it doesn't truncate where `tsc_to_ns()` would. Like
+    /// `tsc_to_ns()`, it may be 1ns below the exact division of `tsc_to_duration()`
+    pub const fn tsc_to_fixed(&self, raw: u64) -> (u64, u32) {
+        let ns = self.wide_tsc_to_ns(raw);
+        ((ns / 1_000_000_000) as u64, (ns % 1_000_000_000) as u32)
+    }
+
+    #[must_use]
+    pub fn now_duration(&self) -> Duration {
+        self.tsc_to_duration(self.read())
+    }
+
+    pub fn elapsed_duration(&self, start_tsc: u64) -> Duration {
+        let delta = self.cycles_between(start_tsc, self.read()).unwrap_or(0);
+        self.tsc_to_duration(delta)
+    }
+
+    /// Busy-waits for `d`, for delays too short or too precise for `thread::sleep()` (e.g. pacing
+    /// packet transmission). Burns the core for the whole wait
+    pub fn spin_for(&self, d: Duration) {
+        self.spin_until(self.deadline_in(d));
+    }
+
+    /// The absolute TSC value `d` from now, for keeping deadlines (e.g. in a timer wheel) in raw
+    /// cycles. Saturates at `u64::MAX` rather than wrapping; on a counter narrower than 64 bits
+    /// (see `counter_bits()`) it wraps like the counter, at most half the range ahead
+    pub fn deadline_in(&self, d: Duration) -> u64 {
+        let ticks = self.duration_to_ticks(d);
+        let now = self.read();
+        if self.counter_bits == 64 {
+            return now.saturating_add(ticks);
+        }
+        let mask = self.counter_mask();
+        now.wrapping_add(ticks.min(mask / 2)) & mask
+    }
+
+    /// Whether the TSC has reached `deadline_tsc` (see `deadline_in()`)
+    #[inline]
+    pub fn reached(&self, deadline_tsc: u64) -> bool {
+        let now = self.read();
+        self.cycles_between(deadline_tsc, now).is_some()
+    }
+
+    /// Busy-waits until the counter reaches `target_tsc` (see `reached()`), e.g. a deadline from
+    /// `deadline_in()`. Burns the core for the whole wait
+    #[inline]
+    pub fn spin_until(&self, target_tsc: u64) {
+        while !self.reached(target_tsc) {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Converts a raw `read_tsc()` value to nanoseconds. The result is truncated to u64, which
+    /// only loses bits when the count spans more than ~584 years worth of nanoseconds; use
+    /// `saturating_tsc_to_ns()` to clamp instead
+    ///
+    /// The conversion multiplies by a reciprocal precomputed at construction instead of dividing.
+    /// The reciprocal is truncated to 64 fractional bits, so the result may be 1ns lower than
+    /// the exact `raw * 1e9 / freq`, but never further off across the whole u64 range
+    pub const fn tsc_to_ns(&self, raw: u64) -> u64 {
+        self.wide_tsc_to_ns(raw) as u64
+    }
+
+    /// Like `tsc_to_ns()`, but returns `u64::MAX` instead of wrapping when the nanoseconds don't
+    /// fit in a u64, regardless of the build profile
+    pub fn saturating_tsc_to_ns(&self, raw: u64) -> u64 {
+        self.wide_tsc_to_ns(raw).try_into().unwrap_or(u64::MAX)
+    }
+
+    #[must_use]
+    pub fn saturating_now_ns(&self) -> u64 {
+        self.saturating_tsc_to_ns(self.read())
+    }
+
+    /// Converts `raw` ticks at `freq` Hz to nanoseconds by exact division, for building const
+    /// tables without constructing a `TSC`. Panics if `freq` is zero
+    pub const fn tsc_to_ns_const(raw: u64, freq: u64) -> u64 {
+        (raw as u128 * 1_000_000_000 / freq as u128) as u64
+    }
+
+    /// Converts a whole slice with `tsc_to_ns()`; the loop is simple enough to be autovectorized.
+    /// Panics if the slices differ in length
+    pub fn tsc_to_ns_batch(&self, raw: &[u64], out: &mut [u64]) {
+        assert_eq!(
+            raw.len(),
+            out.len(),
+            "input and output slices differ in length"
+        );
+        for (ns, &raw) in out.iter_mut().zip(raw) {
+            *ns = self.tsc_to_ns(raw);
+        }
+    }
+
+    /// In-place variant of `tsc_to_ns_batch()`
+    pub fn tsc_to_ns_in_place(&self, values: &mut [u64]) {
+        for value in values {
+            *value = self.tsc_to_ns(*value);
+        }
+    }
+
+    /// Converts a count of `raw` ticks at this clock's frequency to the equivalent count at
+    /// `other`'s, e.g. for merging traces from independently calibrated machines. Wraps if the
+    /// result doesn't fit in a u64
+    pub const fn rescale_to(&self, other: &TSC, raw: u64) -> u64 {
+        // `raw` times the ratio of the tick lengths, which fits in a u128 even unreduced
+        let numer = self.tick_numer as u128 * other.tick_denom as u128;
+        let denom = self.tick_denom as u128 * other.tick_numer as u128;
+        match (raw as u128).checked_mul(numer) {
+            Some(product) => (product / denom) as u64,
+            // only for ratios too odd to multiply exactly in 128 bits; off by at most a tick
+            None => other.ns_to_tsc(self.tsc_to_ns(raw)),
+        }
+    }
+
+    /// The inverse of `tsc_to_ns()`: converts nanoseconds to a count of TSC ticks. Above 1 GHz
+    /// the ticks in a large `ns` may not fit in a u64, so the result saturates at `u64::MAX`
+    /// rather than wrapping
+    pub const fn ns_to_tsc(&self, ns: u64) -> u64 {
+        // can't overflow: both factors fit in a u64
+        let ticks = ns as u128 * self.tick_denom as u128 / self.tick_numer as u128;
+        if ticks > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            ticks as u64
+        }
+    }
+
+    // the ticks in `d`, saturating at `u64::MAX`
+    pub(crate) fn duration_to_ticks(&self, d: Duration) -> u64 {
+        match d.as_nanos().checked_mul(self.tick_denom as u128) {
+            Some(product) => (product / self.tick_numer as u128)
+                .try_into()
+                .unwrap_or(u64::MAX),
+            None => u64::MAX,
+        }
+    }
+
+    // `raw * m >> NS_MULT_SHIFT` with `m = (1e9 << NS_MULT_SHIFT) / freq`, in two halves: `m`
+    // itself needs up to 94 bits (at 1 Hz), so the full product wouldn't fit in a u128
+    #[inline(always)]
+    const fn wide_tsc_to_ns(&self, raw: u64) -> u128 {
+        let frac = (raw as u128 * self.ns_mult_frac as u128) >> NS_MULT_SHIFT;
+        raw as u128 * self.ns_mult_int as u128 + frac
+    }
+
+    fn tsc_to_duration(&self, tsc: u64) -> Duration {
+        let (secs, nanos) = self.split_secs(tsc);
+        Duration::new(secs, nanos)
+    }
+
+    // whole seconds and the sub-second remainder are computed separately, with the remainder
+    // scaled in u128, so no intermediate product can overflow for any TSC value
+    fn split_secs(&self, tsc: u64) -> (u64, u32) {
+        let ns = tsc as u128 * self.tick_numer as u128 / self.tick_denom as u128;
+        ((ns / 1_000_000_000) as u64, (ns % 1_000_000_000) as u32)
+    }
+}
+
+/// The checked counterpart of `TSC::from_freq()`: the `u64` is the frequency in Hz
+impl TryFrom<u64> for TSC {
+    type Error = Error;
+
+    fn try_from(freq: u64) -> Result<Self, Error> {
+        if freq == 0 {
+            return Err(Error::ImplausibleFrequency(0));
+        }
+        Ok(Self::from_freq(freq).with_start_now())
+    }
+}
+
+/// The frequency in Hz, as from `get_freq()`, for code generic over `Into<u64>`
+impl From<TSC> for u64 {
+    fn from(tsc: TSC) -> u64 {
+        tsc.get_freq()
+    }
+}
+
+impl From<&TSC> for u64 {
+    fn from(tsc: &TSC) -> u64 {
+        tsc.get_freq()
+    }
+}
+
+// every field, plus the frequency in GHz and the resolution, which are easier to read in logs
+impl core::fmt::Debug for TSC {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TSC")
+            .field("freq", &self.freq)
+            .field("ns_per_tick", &(self.tick_numer, self.tick_denom))
+            .field("freq_ghz", &self.freq_ghz())
+            .field("resolution_ns", &self.resolution_ns())
+            .field("ns_mult_int", &self.ns_mult_int)
+            .field("ns_mult_frac", &self.ns_mult_frac)
+            .field("start", &self.start)
+            .field("fence", &self.fence)
+            .field("counter_bits", &self.counter_bits)
+            .field("source", &self.source)
+            .field("uncertainty_ppm", &self.uncertainty_ppm)
+            .finish()
+    }
+}
+
+// the other fields are derived from the tick length or only affect how the counter is read.
+// It's kept in lowest terms, so equal rates have equal fields
+impl PartialEq for TSC {
+    fn eq(&self, other: &Self) -> bool {
+        self.ns_per_tick() == other.ns_per_tick()
+    }
+}
+
+impl Eq for TSC {}
+
+impl core::hash::Hash for TSC {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.ns_per_tick().hash(state);
+    }
+}
+
+const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// the fractional bits of the ns-per-cycle multiplier. Truncating it loses less than 2^-64ns per
+// cycle, which adds up to less than 1ns over any u64 count of cycles, so conversions are at most
+// 1ns below exact; fewer bits would let the error grow with `raw`, and the fraction is kept in a
+// u64, so more don't fit
+const NS_MULT_SHIFT: u32 = 64;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+const CACHE_UNKNOWN: u8 = 0;
+
+// a CPUID feature bit, probed on first use and cached in `cell` (which starts as `CACHE_UNKNOWN`)
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+fn cached_cpu_flag(cell: &core::sync::atomic::AtomicU8, probe: impl FnOnce() -> bool) -> bool {
+    use core::sync::atomic::Ordering;
+    const ABSENT: u8 = 1;
+    const PRESENT: u8 = 2;
+
+    match cell.load(Ordering::Relaxed) {
+        CACHE_UNKNOWN => {
+            let present = probe();
+            cell.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+            present
+        }
+        state => state == PRESENT,
+    }
+}
+
+/// `TSC::read_tsc()` as a free function, e.g. for passing around as `tsc::read_tsc as fn() -> u64`
+#[inline(always)]
+#[must_use]
+pub fn read_tsc() -> u64 {
+    TSC::read_tsc()
+}
+
+/// `now_ns()` of the process-wide `TSC::global()` clock
+#[cfg(feature = "std")]
+#[must_use]
+pub fn now_ns() -> u64 {
+    TSC::global().now_ns()
+}
+
+#[test]
+fn test_perf() {
+    use std::time::Instant;
+    // hosts without the CPUID frequency leaves (e.g. some VMs) can't detect the frequency
+    let t = match TSC::new() {
+        Ok(t) => t,
+        Err(err) => {
+            println!("skipping: {err}");
+            return;
+        }
+    };
+
+    let t0 = Instant::now();
+    let mut counter = 0;
+    for _ in 0..100_000 {
+        let tmp = Instant::now();
+        counter += tmp.duration_since(t0).as_nanos();
+    }
+    let t1 = Instant::now();
+    assert!(counter > 10);
+    println!("{:?}", t1.duration_since(t0));
+
+    let t0 = Instant::now();
     let mut counter = 0;
     for _ in 0..100_000 {
         let tmp = t.now_ns();
@@ -127,17 +1909,755 @@ fn test_perf() {
 }
 
 #[test]
-fn test_skew() {
-    use std::time::{Duration, Instant};
-    let t = TSC::new().unwrap();
-    println!("{t:?}");
+fn test_capabilities() {
+    let caps = TSC::capabilities();
+    assert!(caps.has_tsc);
+    if TSC::new().is_ok() {
+        assert!(caps.has_invariant_tsc);
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+#[test]
+fn test_extended_leaf_edx() {
+    let max_extended = arch::__cpuid(0x80000000).eax;
+    // no CPU defines extended leaves anywhere near this high
+    assert!(max_extended < 0x8000_ffff);
+    assert_eq!(TSC::extended_leaf_edx(0x8000_ffff), 0);
+    if max_extended >= 0x80000001 {
+        assert_eq!(
+            TSC::extended_leaf_edx(0x80000001),
+            arch::__cpuid(0x80000001).edx
+        );
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+#[test]
+fn test_parse_tsc_flags() {
+    let cpuinfo =
+        "processor\t: 0\nflags\t\t: fpu tsc constant_tsc rep_good nopl\nbugs\t\t: spectre_v1\n";
+    assert_eq!(TSC::parse_tsc_flags(cpuinfo), Some((true, false)));
+    assert_eq!(TSC::parse_tsc_flags("processor\t: 0\n"), None);
+
+    let caps = TSC::capabilities();
+    assert!(caps.has_constant_tsc.is_some() && caps.has_nonstop_tsc.is_some());
+}
+
+#[test]
+fn test_read_tsc_with() {
+    let mut prev = 0;
+    for fence in [
+        Fence::None,
+        Fence::Lfence,
+        Fence::MfenceLfence,
+        Fence::Rdtscp,
+    ] {
+        if fence == Fence::Rdtscp && !TSC::has_rdtscp() {
+            continue;
+        }
+        let tsc = TSC::read_tsc_with(fence);
+        assert!(tsc > prev);
+        prev = tsc;
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+#[test]
+fn test_freq_from_kernel() {
+    let Some(freq) = TSC::freq_from_kernel() else {
+        return;
+    };
+    let calibrated = TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap();
+    assert!(
+        freq.abs_diff(calibrated.get_freq()) < freq / 100,
+        "{freq} {calibrated:?}"
+    );
+}
+
+#[test]
+fn test_duration() {
+    let t = TSC::from_freq(3_000_000_000);
+    assert_eq!(t.tsc_to_duration(0), Duration::ZERO);
+    assert_eq!(
+        t.tsc_to_duration(4_500_000_000),
+        Duration::from_millis(1500)
+    );
+    assert_eq!(
+        t.tsc_to_duration(u64::MAX),
+        Duration::new(u64::MAX / 3_000_000_000, 236_517_205)
+    );
+}
+
+#[test]
+fn test_spin() {
+    let t = TSC::from_freq(1_000_000_000);
+    let target = TSC::read_tsc() + 10_000;
+    t.spin_until(target);
+    assert!(TSC::read_tsc() >= target);
+
+    // the raw counter is almost surely past any 32-bit target, the clock's masked reads aren't
+    let narrow = TSC {
+        counter_bits: 32,
+        ..t
+    };
+    let start = narrow.start();
+    narrow.spin_until((start + 10_000) & narrow.counter_mask());
+    assert!(narrow.cycles_between(start, narrow.start()).unwrap() >= 10_000);
+
+    let start = TSC::read_tsc();
+    t.spin_for(Duration::from_micros(10));
+    assert!(TSC::read_tsc() - start >= 10_000);
+}
+
+#[test]
+fn test_deadline() {
+    let t = TSC::from_freq(1_000_000_000);
+    let deadline = t.deadline_in(Duration::from_micros(10));
+    assert!(deadline >= TSC::read_tsc());
+    t.spin_until(deadline);
+    assert!(t.reached(deadline));
+    assert!(!t.reached(t.deadline_in(Duration::from_secs(3600))));
+    assert_eq!(t.deadline_in(Duration::MAX), u64::MAX);
+}
+
+#[test]
+fn test_error_display() {
+    assert_eq!(Error::TscNotSupported.to_string(), "the CPU has no TSC");
+    assert_eq!(
+        Error::ImplausibleFrequency(42).to_string(),
+        "implausible TSC frequency: 42 Hz"
+    );
+    assert_eq!(
+        Error::SourceUnavailable(FreqSource::Cpuid15).to_string(),
+        "frequency source Cpuid15 is unavailable"
+    );
+    let err: &dyn core::error::Error = &Error::CalibrationFailed;
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_freq_source() {
+    let given = TSC::from_freq(3_000_000_000);
+    assert_eq!(given.freq_source(), None);
+    assert!(!given.is_nominal());
+
+    let calibrated = TSC::calibrate(Duration::from_millis(10)).unwrap();
+    assert_eq!(calibrated.freq_source(), Some(FreqSource::Calibrated));
+    assert!(!calibrated.is_nominal());
+
+    if let Ok((t, source)) = TSC::new_with_source() {
+        assert_eq!(t.freq_source(), Some(source));
+        assert_eq!(t.is_nominal(), source == FreqSource::Cpuid16);
+    }
+    if let Ok(t) = TSC::builder().frequency_source(FreqSource::Cpuid16).build() {
+        assert!(t.is_nominal());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_monotonic_raw_ns() {
+    let t = TSC::calibrate(Duration::from_millis(10)).unwrap();
+    let (m0, n0) = (TSC::monotonic_raw_ns(), t.now_ns());
+    std::thread::sleep(Duration::from_millis(20));
+    let (m1, n1) = (TSC::monotonic_raw_ns(), t.now_ns());
+    assert!(m1 > m0);
+    let drift = (n1 - n0).abs_diff(m1 - m0);
+    assert!(drift < 5_000_000, "drift={drift}ns");
+}
+
+#[test]
+fn test_from_ns_per_tick() {
+    // Apple Silicon's timebase: 125/3 ns per tick, exactly 24 MHz
+    let apple = TSC::from_ns_per_tick(125, 3).unwrap();
+    assert_eq!(apple, TSC::from_freq(24_000_000));
+    assert_eq!(apple.get_freq(), 24_000_000);
+
+    // 7/3 ns per tick is 428571428.57 Hz: conversions use the ratio, not the rounded frequency
+    let t = TSC::from_ns_per_tick(14, 6).unwrap();
+    assert_eq!(t.ns_per_tick(), (7, 3));
+    assert_eq!(t.get_freq(), 428_571_429);
+    assert_eq!(t.tsc_to_duration(3_000_000_000), Duration::from_secs(7));
+    assert_eq!(t.tsc_to_duration(3), Duration::from_nanos(7));
+    assert!(7_000_000_000 - t.tsc_to_ns(3_000_000_000) <= 1);
+    assert_eq!(t.ns_to_tsc(7_000_000_000), 3_000_000_000);
+    assert_eq!(t.rescale_to(&TSC::from_freq(3_000_000_000), 3), 21);
+    assert!((t.ns_per_cycle() - 7.0 / 3.0).abs() < 1e-12);
+    assert_eq!(t.self_check(), Ok(()));
+    assert_ne!(t, TSC::from_freq(428_571_429));
+
+    assert_eq!(
+        TSC::from_ns_per_tick(1, 0).unwrap_err(),
+        Error::ImplausibleFrequency(0)
+    );
+    assert!(TSC::from_ns_per_tick(0, 1).is_err());
+    assert!(TSC::from_ns_per_tick(2_000_000_000, 1).is_err());
+    assert!(TSC::from_ns_per_tick(1, 10_000).is_err());
+}
+
+#[test]
+fn test_self_check() {
+    for freq in [1, 24_000_000, 1_000_000_000, 2_999_999_999, 10_000_000_000] {
+        assert_eq!(TSC::from_freq(freq).self_check(), Ok(()), "freq={freq}");
+    }
+    assert_eq!(
+        Error::NotMonotonic {
+            before: 10,
+            after: 9
+        }
+        .to_string(),
+        "now_ns() went backwards from 10 to 9"
+    );
+}
+
+#[test]
+fn test_has_tsc_deadline() {
+    let deadline = TSC::has_tsc_deadline();
+    println!("tsc_deadline={deadline}");
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "x86")))]
+    if let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") {
+        let flags = cpuinfo.lines().find(|line| line.starts_with("flags"));
+        // the kernel can hide the flag (e.g. on CPUs with buggy microcode), but not invent it
+        if flags.is_some_and(|flags| flags.split_whitespace().any(|f| f == "tsc_deadline_timer")) {
+            assert!(deadline);
+        }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    assert!(!deadline);
+}
+
+#[test]
+fn test_frequencies_mhz() {
+    match TSC::frequencies_mhz() {
+        Some((base, max, bus)) => {
+            println!("base={base}MHz max={max}MHz bus={bus}MHz");
+            assert!(base > 0);
+            assert!(max == 0 || max >= base);
+        }
+        None => println!("leaf 0x16 unavailable"),
+    }
+}
+
+#[test]
+fn test_debug() {
+    let t = TSC::from_freq(2_500_000_000);
+    let debug = format!("{t:?}");
+    assert!(debug.starts_with(
+        "TSC { freq: 2500000000, ns_per_tick: (2, 5), freq_ghz: 2.5, resolution_ns: 0.4,"
+    ));
+    assert!(debug.contains(&format!("fence: {:?}", TSC::DEFAULT_FENCE)));
+    assert!(debug.contains("source: None"));
+    assert_eq!(
+        format!("{:?}", Error::ImplausibleFrequency(7)),
+        "ImplausibleFrequency(7)"
+    );
+}
+
+#[test]
+fn test_eq_hash() {
+    use std::collections::HashSet;
+
+    let a = TSC::from_freq(3_000_000_000);
+    let b = TSC::from_freq(3_000_000_000).with_start_now();
+    let c = TscBuilder::new()
+        .calibrate(Duration::from_millis(10))
+        .default_fence(Fence::Rdtscp)
+        .counter_bits(56)
+        .build()
+        .unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, TSC::from_freq(2_000_000_000));
+
+    let set: HashSet<TSC> = [a, b, c, TSC::from_freq(c.get_freq())].into();
+    assert_eq!(set.len(), if c == a { 1 } else { 2 });
+}
+
+#[test]
+fn test_counter_bits() {
+    let wide = TSC::from_freq(1_000_000_000);
+    assert_eq!(wide.counter_bits(), 64);
+    assert_eq!(wide.delta_ns(u64::MAX - 10, 5), None);
+
+    let narrow = TscBuilder::new()
+        .calibrate(Duration::from_millis(10))
+        .counter_bits(56)
+        .build()
+        .unwrap();
+    assert_eq!(narrow.counter_bits(), 56);
+    let top = (1 << 56) - 1;
+    let cycles = narrow.get_freq() / 1_000_000;
+    let expected = narrow.tsc_to_ns(cycles + 1);
+    assert_eq!(narrow.delta_ns(top, cycles), Some(expected));
+    assert_eq!(narrow.delta_ns(cycles, top), None);
+    assert_eq!(narrow.delta_ns_saturating(cycles, top), 0);
+
+    let deadline = narrow.deadline_in(Duration::MAX);
+    assert!(deadline <= top);
+    assert!(!narrow.reached(deadline));
+    narrow.spin_for(Duration::from_micros(10));
+    assert!(narrow.now_ns() <= narrow.tsc_to_ns(top));
+}
+
+#[test]
+fn test_now_ns_noinline() {
+    let t = TSC::from_freq(1_000_000_000);
+    let n0 = t.now_ns();
+    let n1 = t.now_ns_noinline();
+    assert!(n1 >= n0);
+    assert!(t.now_ns() >= n1);
+}
+
+#[test]
+fn test_fixed() {
+    let t = TSC::from_freq(2_400_000_000);
+    for raw in [
+        0,
+        1,
+        2_399_999_999,
+        2_400_000_000,
+        0x1234_5678_9abc_def0,
+        u64::MAX,
+    ] {
+        let (secs, nanos) = t.tsc_to_fixed(raw);
+        assert_eq!(secs * 1_000_000_000 + nanos as u64, t.tsc_to_ns(raw));
+        let exact = t.tsc_to_duration(raw) - Duration::new(secs, nanos);
+        assert!(exact <= Duration::from_nanos(1));
+    }
+    // at 100MHz the nanoseconds overflow u64, but the split doesn't
+    assert_eq!(
+        TSC::from_freq(100_000_000).tsc_to_fixed(u64::MAX),
+        (184_467_440_737, 95_516_150)
+    );
+
+    let before = t.now_ns();
+    let (secs, nanos) = t.now_fixed();
+    let after = t.now_ns();
+    assert!((before..=after).contains(&(secs * 1_000_000_000 + nanos as u64)));
+}
+
+#[test]
+fn test_ns_mul_shift_random() {
+    // splitmix64, so the inputs are the same on every run
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    for _ in 0..1_000 {
+        let freq = match next() % 4 {
+            0 => next() | 1,
+            _ => 1_000_000 + next() % 10_000_000_000,
+        };
+        let t = TSC::from_freq(freq);
+        for _ in 0..100 {
+            let raw = next() >> (next() % 64);
+            let exact = raw as u128 * 1_000_000_000 / freq as u128;
+            let ns = t.wide_tsc_to_ns(raw);
+            assert!(ns <= exact && exact - ns <= 1, "freq={freq} raw={raw}");
+        }
+    }
+}
+
+#[test]
+fn test_ns_overflow() {
+    let exact = |t: &TSC, tsc: u64| (tsc as u128 * 1_000_000_000 / t.get_freq() as u128) as u64;
+
+    let t = TSC::from_freq(3_000_000_000);
+    let mut prev = 0;
+    for tsc in (u64::MAX - 10_000..=u64::MAX).step_by(7) {
+        let ns = t.tsc_to_ns(tsc);
+        assert!(exact(&t, tsc) - ns <= 1);
+        assert!(ns >= prev);
+        prev = ns;
+    }
+
+    let t = TSC::from_freq(u64::MAX);
+    assert_eq!(t.tsc_to_ns(u64::MAX - 1), 999_999_999);
+
+    let t = TSC::from_freq(24_000_000);
+    assert!(exact(&t, u64::MAX).wrapping_sub(t.tsc_to_ns(u64::MAX)) <= 1);
+}
+
+#[test]
+fn test_freq_units() {
+    let t = TSC::from_freq(2_400_000_000);
+    assert_eq!(t.freq_mhz(), 2_400.0);
+    assert_eq!(t.freq_ghz(), 2.4);
+    assert_eq!(t.freq_human(), "2.40 GHz");
+    assert_eq!(TSC::from_freq(24_000_000).freq_human(), "24.00 MHz");
+    assert_eq!(TSC::from_freq(32_768).freq_human(), "32.77 kHz");
+    assert_eq!(TSC::from_freq(10).freq_human(), "10 Hz");
+}
+
+#[test]
+fn test_cycles_per_ns() {
+    let t = TSC::from_freq(2_500_000_000);
+    assert_eq!(t.cycles_per_ns(), 2.5);
+    assert_eq!(t.ns_per_cycle(), 0.4);
+}
+
+#[test]
+fn test_resolution_ns() {
+    assert!((TSC::from_freq(24_000_000).resolution_ns() - 41.666).abs() < 0.001);
+    assert!(TSC::from_freq(3_000_000_000).resolution_ns() < 1.0);
+}
+
+#[test]
+fn test_saturating_ns() {
+    let t = TSC::from_freq(3_000_000_000);
+    assert_eq!(t.saturating_tsc_to_ns(u64::MAX), t.tsc_to_ns(u64::MAX));
+    assert!(t.saturating_now_ns() > 0);
+
+    let t = TSC::from_freq(24_000_000);
+    assert_eq!(t.saturating_tsc_to_ns(u64::MAX), u64::MAX);
+    assert_ne!(t.tsc_to_ns(u64::MAX), u64::MAX);
+    let max_tsc = t.ns_to_tsc(u64::MAX);
+    assert_eq!(t.saturating_tsc_to_ns(max_tsc), t.tsc_to_ns(max_tsc));
+    assert_eq!(t.saturating_tsc_to_ns(max_tsc + 1), u64::MAX);
+}
+
+#[test]
+fn test_tsc_to_ns_batch() {
+    let t = TSC::from_freq(2_400_000_000);
+    let raw: Vec<u64> = (0..1000u64).map(|i| i * 0x1234_5678_9abc).collect();
+    let mut out = vec![0; raw.len()];
+    t.tsc_to_ns_batch(&raw, &mut out);
+    let expected: Vec<u64> = raw.iter().map(|&r| t.tsc_to_ns(r)).collect();
+    assert_eq!(out, expected);
+
+    let mut values = raw.clone();
+    t.tsc_to_ns_in_place(&mut values);
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn test_const() {
+    const TSC_3GHZ: TSC = TSC::from_freq(3_000_000_000);
+    const FREQ: u64 = TSC_3GHZ.get_freq();
+    const NS: u64 = TSC_3GHZ.tsc_to_ns(4_500_000_000);
+    const TICKS: u64 = TSC_3GHZ.ns_to_tsc(1_500_000_000);
+    const TABLE: [u64; 3] = [
+        TSC::tsc_to_ns_const(0, FREQ),
+        TSC::tsc_to_ns_const(3, FREQ),
+        TSC::tsc_to_ns_const(3_000_000_000, FREQ),
+    ];
+    assert_eq!(FREQ, 3_000_000_000);
+    const { assert!(1_500_000_000 - NS <= 1) };
+    assert_eq!(TICKS, 4_500_000_000);
+    assert_eq!(TABLE, [0, 1, 1_000_000_000]);
+}
+
+#[test]
+fn test_rescale_to() {
+    let a = TSC::from_freq(2_000_000_000);
+    let b = TSC::from_freq(3_000_000_000);
+    assert_eq!(a.rescale_to(&b, 2_000_000_000), 3_000_000_000);
+    assert_eq!(b.rescale_to(&a, 3_000_000_000), 2_000_000_000);
+    assert_eq!(a.rescale_to(&a, u64::MAX), u64::MAX);
+    let raw = 123_456_789_012;
+    assert!(
+        b.tsc_to_ns(a.rescale_to(&b, raw))
+            .abs_diff(a.tsc_to_ns(raw))
+            <= 1
+    );
+}
+
+#[test]
+fn test_ns_to_tsc() {
+    let t = TSC::from_freq(2_400_000_000);
+    assert_eq!(t.ns_to_tsc(1_500_000_000), 3_600_000_000);
+    assert_eq!(
+        t.ns_to_tsc(u64::MAX / 4),
+        (u64::MAX as u128 / 4 * 24 / 10) as u64
+    );
+    for ns in [0, 1, 999, 123_456_789, 86_400_000_000_000] {
+        assert!(t.tsc_to_ns(t.ns_to_tsc(ns)).abs_diff(ns) <= 1);
+    }
+    assert_eq!(t.ns_to_tsc(u64::MAX), u64::MAX);
+    assert_eq!(t.ns_to_tsc(u64::MAX / 12 * 5), u64::MAX / 12 * 12);
+}
+
+#[test]
+fn test_units() {
+    let t = TSC::from_freq(2_400_000_000);
+    let ns0 = t.now_ns();
+    let (micros, secs) = (t.now_micros(), t.now_secs());
+    let ns1 = t.now_ns();
+    assert!(ns0 / 1000 <= micros && micros <= ns1 / 1000);
+    assert!(ns0 / 1_000_000_000 <= secs && secs <= ns1 / 1_000_000_000);
+
+    assert_eq!(t.tsc_to_micros(2_399), 0);
+    assert_eq!(t.tsc_to_micros(2_400), 1);
+    // at 1 MHz the nanoseconds in `u64::MAX` ticks overflow a u64, but the microseconds don't
+    let slow = TSC::from_freq(1_000_000);
+    assert_eq!(slow.tsc_to_micros(u64::MAX), u64::MAX);
+}
+
+#[test]
+fn test_now_f64_since_start() {
+    let t = TSC::from_freq(2_000_000_000).with_start_now();
+    let secs = t.now_f64_since_start();
+    assert!((0.0..1.0).contains(&secs));
+    assert!(t.now_f64_since_start() >= secs);
+    assert!(t.now_f64() > secs);
+}
+
+#[test]
+fn test_elapsed_ns() {
+    let t = TSC::from_freq(1_000_000_000);
+    let start = t.start();
+    assert!(t.elapsed_ns(start) < 1_000_000_000);
+    assert_eq!(t.elapsed_ns(u64::MAX), 0);
+}
+
+#[test]
+fn test_read_tsc_fn() {
+    let read: fn() -> u64 = read_tsc;
+    let t0 = read();
+    assert!(TSC::read_tsc() >= t0);
+}
+
+#[test]
+fn test_read_tsc_checked() {
+    #[cfg(all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        feature = "fence-rdtscp"
+    ))]
+    if !TSC::has_rdtscp() {
+        assert_eq!(TSC::read_tsc_checked(), Err(Error::RdtscpNotSupported));
+        return;
+    }
+    let t0 = TSC::read_tsc_checked().unwrap();
+    assert!(TSC::read_tsc_checked().unwrap() >= t0);
+}
+
+#[test]
+fn test_read_tsc_bracketed() {
+    let t0 = TSC::read_tsc_bracketed();
+    let t1 = TSC::read_tsc_bracketed();
+    assert!(t1 >= t0);
+}
+
+#[test]
+fn test_delta_ns() {
+    let t = TSC::from_freq(2_000_000_000);
+    assert_eq!(t.delta_ns(1_000, 3_000), Some(1_000));
+    assert_eq!(t.delta_ns(3_000, 1_000), None);
+    assert_eq!(t.delta_ns_saturating(1_000, 3_000), 1_000);
+    assert_eq!(t.delta_ns_saturating(3_000, 1_000), 0);
+}
+
+#[test]
+fn test_measure() {
+    let (sum, cycles) = TSC::measure(|| (0..1000u64).sum::<u64>());
+    assert_eq!(sum, 499_500);
+    assert!(cycles > 0);
+
+    let t = TSC::from_freq(1_000_000_000);
+    let ((), ns) = t.measure_ns(|| std::thread::sleep(std::time::Duration::from_millis(1)));
+    assert!(ns > 0);
+}
+
+#[test]
+fn test_measure_compensated() {
+    let overhead = TSC::estimate_overhead();
+    assert!(overhead > 0);
+    assert_eq!(TSC::estimate_overhead(), overhead);
+
+    let (sum, _) = TSC::measure_compensated(|| (0..1000u64).sum::<u64>());
+    assert_eq!(sum, 499_500);
+}
+
+#[test]
+fn test_new_with_source() {
+    let (tsc, source) = TSC::new_with_source().unwrap();
+    match TSC::new() {
+        Ok(detected) => assert_eq!(tsc.get_freq(), detected.get_freq()),
+        Err(_) => assert_eq!(source, FreqSource::Calibrated),
+    }
+}
+
+#[test]
+fn test_plausible_freq() {
+    assert_eq!(TSC::plausible_freq(2_400_000_000), Ok(2_400_000_000));
+    assert_eq!(
+        TSC::plausible_freq(40_000_000_000),
+        Err(Error::ImplausibleFrequency(40_000_000_000))
+    );
+    assert_eq!(TSC::plausible_freq(0), Err(Error::ImplausibleFrequency(0)));
+    // a 24 MHz generic timer is normal, but a 24 MHz TSC is garbage
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    assert_eq!(TSC::plausible_freq(24_000_000), Ok(24_000_000));
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    assert_eq!(
+        TSC::plausible_freq(24_000_000),
+        Err(Error::ImplausibleFrequency(24_000_000))
+    );
+    if let Ok(freq) = TSC::cpu_freq() {
+        assert!(TSC::PLAUSIBLE_FREQ.contains(&freq));
+    }
+}
+
+#[test]
+fn test_from_freq() {
+    let t = TSC::from_freq(2_400_000_000);
+    assert_eq!(t.get_freq(), 2_400_000_000);
+    assert!(t.now_ns() > 0);
+}
+
+#[test]
+fn test_try_from() {
+    assert_eq!(
+        TSC::try_from(2_400_000_000).unwrap().get_freq(),
+        2_400_000_000
+    );
+    assert_eq!(
+        TSC::try_from(0).unwrap_err(),
+        Error::ImplausibleFrequency(0)
+    );
+}
+
+#[test]
+fn test_into_u64() {
+    let t = TSC::from_freq(2_400_000_000);
+    let hz: u64 = (&t).into();
+    assert_eq!(hz, 2_400_000_000);
+    assert_eq!(u64::from(t), t.get_freq());
+}
+
+#[test]
+#[should_panic]
+fn test_from_freq_zero() {
+    let _ = TSC::from_freq(0);
+}
+
+#[test]
+fn test_calibrate() {
+    let t = TSC::calibrate(TSC::DEFAULT_CALIBRATION_WINDOW).unwrap();
+    let n0 = t.now_ns();
+    let t0 = Instant::now();
+    std::thread::sleep(Duration::from_millis(50));
+    let tsc_dt = t.now_ns() - n0;
+    let clock_dt = t0.elapsed().as_nanos() as u64;
+    assert!(
+        tsc_dt.abs_diff(clock_dt) < clock_dt / 20,
+        "tsc={tsc_dt} clock={clock_dt}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_calibrate_against_monotonic() {
+    let t = TSC::calibrate_against_monotonic(Duration::from_millis(50)).unwrap();
+    assert_eq!(t.freq_source(), Some(FreqSource::Calibrated));
+    let (m0, n0) = (TSC::clock_ns(libc::CLOCK_MONOTONIC), t.now_ns());
+    std::thread::sleep(Duration::from_millis(50));
+    let (m1, n1) = (TSC::clock_ns(libc::CLOCK_MONOTONIC), t.now_ns());
+    let (tsc_dt, clock_dt) = (n1 - n0, m1 - m0);
+    assert!(
+        tsc_dt.abs_diff(clock_dt) < clock_dt / 20,
+        "tsc={tsc_dt} clock={clock_dt}"
+    );
+}
+
+#[test]
+fn test_read_pair() {
+    let before = TSC::read_tsc_bracketed();
+    let (first, second) = TSC::read_pair();
+    assert!(before <= first && first <= second);
+    println!("read latency={} cycles", second - first);
+}
+
+#[test]
+fn test_calibrate_refined() {
+    let cal = TSC::calibrate_refined(3, Duration::from_millis(10)).unwrap();
+    assert!(cal.tsc.get_freq() > 0);
+    assert!(cal.ppm_error >= 0.0);
+    let uncertainty = cal.tsc.freq_uncertainty_ppm().unwrap();
+    assert!((0.0..10_000.0).contains(&uncertainty), "{uncertainty}ppm");
+    println!("{cal:?}");
+
+    let single = TSC::calibrate_refined(1, Duration::from_millis(10)).unwrap();
+    assert_eq!(single.tsc.freq_uncertainty_ppm(), None);
+    assert_eq!(TSC::from_freq(3_000_000_000).freq_uncertainty_ppm(), None);
+
+    assert_eq!(
+        TSC::calibrate_refined(0, Duration::from_millis(10)).unwrap_err(),
+        Error::CalibrationFailed
+    );
+}
+
+#[test]
+fn test_verify_freq() {
+    let t = TSC::calibrate(Duration::from_millis(20)).unwrap();
+    let ppm = t.verify_freq(Duration::from_millis(20)).unwrap();
+    assert!(ppm.abs() < 50_000.0, "{ppm} ppm");
+
+    let doubled = TSC::from_freq(t.get_freq() * 2);
+    let ppm = doubled.verify_freq(Duration::from_millis(20)).unwrap();
+    assert!((ppm + 500_000.0).abs() < 50_000.0, "{ppm} ppm");
+}
+
+#[test]
+fn test_global() {
+    assert!(std::ptr::eq(TSC::global(), TSC::global()));
+    let n0 = now_ns();
+    assert!(now_ns() >= n0);
+}
+
+#[test]
+fn test_global_threads() {
+    let clocks: Vec<&'static TSC> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4).map(|_| s.spawn(TSC::global)).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert!(clocks.iter().all(|&c| std::ptr::eq(c, TSC::global())));
+}
+
+#[test]
+fn test_thread_local() {
+    let freq = TSC::global().get_freq();
+    assert_eq!(TSC::thread_local().get_freq(), freq);
+    let other = std::thread::spawn(|| TSC::thread_local().get_freq());
+    assert_eq!(other.join().unwrap(), freq);
+}
+
+// compares the TSC-derived time across a sleep of `window` against `Instant`'s, failing if they
+// differ by more than 5% (i.e. the detected frequency is grossly wrong)
+#[cfg(test)]
+fn check_skew(window: std::time::Duration) {
+    use std::time::Instant;
+    let (t, source) = TSC::new_with_source().unwrap();
+    println!("{t:?} source={source:?}");
 
     let t0 = Instant::now();
     let n0 = t.now_ns();
-    std::thread::sleep(Duration::from_secs(60));
+    std::thread::sleep(window);
     let n1 = t.now_ns();
-    let tsc_dt = n1 - n0;
-    let clock_dt = Instant::now().duration_since(t0);
+    let clock_dt = t0.elapsed().as_nanos() as f64;
+    let tsc_dt = (n1 - n0) as f64;
 
-    println!("tsc={} clock={clock_dt:?}", tsc_dt as f64 / 1_000_000_000.0,);
+    let skew = (tsc_dt - clock_dt) / clock_dt;
+    println!(
+        "tsc={tsc_dt}ns clock={clock_dt}ns skew={:.4}%",
+        skew * 100.0
+    );
+    assert!(skew.abs() < 0.05, "skew={skew}");
+}
+
+#[test]
+fn test_skew() {
+    check_skew(std::time::Duration::from_millis(200));
+}
+
+// the same over a full minute, which shows small frequency errors; run with `--ignored`
+#[test]
+#[ignore]
+fn test_skew_long() {
+    check_skew(std::time::Duration::from_secs(60));
 }
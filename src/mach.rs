@@ -0,0 +1,24 @@
+#[repr(C)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+unsafe extern "C" {
+    fn mach_absolute_time() -> u64;
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+}
+
+#[inline(always)]
+pub(crate) fn absolute_time() -> u64 {
+    unsafe { mach_absolute_time() }
+}
+
+// (numer, denom) such that one tick lasts numer / denom nanoseconds
+pub(crate) fn timebase() -> Option<(u32, u32)> {
+    let mut info = MachTimebaseInfo { numer: 0, denom: 0 };
+    if unsafe { mach_timebase_info(&mut info) } != 0 || info.numer == 0 || info.denom == 0 {
+        return None;
+    }
+    Some((info.numer, info.denom))
+}
@@ -0,0 +1,54 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::TSC;
+
+/// A `TSC` whose `now_ns()` never goes backwards, even if the calling thread migrates to a core
+/// whose counter lags slightly behind. Every reading is merged into a shared last-seen value with
+/// an atomic max, so timestamps are monotonic (non-decreasing) across all users of this instance
+/// within the process -- not across instances or processes. The shared atomic is contended by
+/// every reader, which makes this noticeably more expensive than `TSC::now_ns()` under load
+#[derive(Debug)]
+pub struct MonotonicTsc {
+    tsc: TSC,
+    last: AtomicU64,
+}
+
+impl MonotonicTsc {
+    pub fn new(tsc: TSC) -> Self {
+        Self {
+            tsc,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get_tsc(&self) -> &TSC {
+        &self.tsc
+    }
+
+    pub fn now_ns(&self) -> u64 {
+        let now = self.tsc.now_ns();
+        let prev = self.last.fetch_max(now, Ordering::AcqRel);
+        prev.max(now)
+    }
+}
+
+#[test]
+fn test_monotonic() {
+    let m = MonotonicTsc::new(TSC::from_freq(3_000_000_000));
+    m.last.store(u64::MAX - 1, Ordering::Relaxed);
+    assert_eq!(m.now_ns(), u64::MAX - 1);
+
+    let m = MonotonicTsc::new(TSC::from_freq(3_000_000_000));
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let mut prev = 0;
+                for _ in 0..10_000 {
+                    let now = m.now_ns();
+                    assert!(now >= prev);
+                    prev = now;
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,103 @@
+use core::ptr::{self, read_volatile};
+use core::sync::atomic::{Ordering, fence};
+
+// the first (PERF_ATTR_SIZE_VER0) revision of `struct perf_event_attr`, which every kernel accepts
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+const PERF_TYPE_SOFTWARE: u32 = 1;
+const PERF_COUNT_SW_DUMMY: u64 = 9;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+
+// field offsets within `struct perf_event_mmap_page`
+const PAGE_LOCK: usize = 8;
+const PAGE_CAPABILITIES: usize = 40;
+const PAGE_TIME_SHIFT: usize = 50;
+const PAGE_TIME_MULT: usize = 52;
+const CAP_USER_TIME: u64 = 1 << 3;
+
+// The kernel publishes its own calibrated TSC-to-ns conversion (`ns = cycles * time_mult >>
+// time_shift`) in the metadata page of any perf event, so that userspace can convert TSC readings
+// the same way it does; a dummy software event is enough, and is allowed under the default
+// `perf_event_paranoid` setting
+pub(crate) fn perf_tsc_freq() -> Option<u64> {
+    let attr = PerfEventAttr {
+        type_: PERF_TYPE_SOFTWARE,
+        size: size_of::<PerfEventAttr>() as u32,
+        config: PERF_COUNT_SW_DUMMY,
+        flags: ATTR_DISABLED | ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV,
+        ..Default::default()
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const PerfEventAttr,
+            0 as libc::pid_t,
+            -1 as libc::c_int,
+            -1 as libc::c_int,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return None;
+    }
+    let fd = fd as libc::c_int;
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let page = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            page_size,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        )
+    };
+    let res = if page == libc::MAP_FAILED {
+        None
+    } else {
+        let res = unsafe { read_time_conversion(page as *const u8) };
+        unsafe { libc::munmap(page, page_size) };
+        res
+    };
+    unsafe { libc::close(fd) };
+
+    let (shift, mult) = res?;
+    let freq = (1_000_000_000u128 << shift) / mult as u128;
+    u64::try_from(freq).ok().filter(|&freq| freq != 0)
+}
+
+// reads (time_shift, time_mult) under the page's seqlock
+unsafe fn read_time_conversion(page: *const u8) -> Option<(u16, u32)> {
+    loop {
+        let seq = unsafe { read_volatile(page.add(PAGE_LOCK) as *const u32) };
+        fence(Ordering::Acquire);
+        let caps = unsafe { read_volatile(page.add(PAGE_CAPABILITIES) as *const u64) };
+        let shift = unsafe { read_volatile(page.add(PAGE_TIME_SHIFT) as *const u16) };
+        let mult = unsafe { read_volatile(page.add(PAGE_TIME_MULT) as *const u32) };
+        fence(Ordering::Acquire);
+        if unsafe { read_volatile(page.add(PAGE_LOCK) as *const u32) } != seq {
+            continue;
+        }
+        if caps & CAP_USER_TIME == 0 || mult == 0 {
+            return None;
+        }
+        return Some((shift, mult));
+    }
+}
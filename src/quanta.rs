@@ -0,0 +1,70 @@
+//! A stand-in for the `quanta` crate's `Clock`, for switching to this crate by changing imports
+
+use core::time::Duration;
+
+use crate::TSC;
+
+/// Mirrors the method names of `quanta::Clock`, so `use quanta::Clock` can become
+/// `use tsc::quanta::Clock`. The mapping is:
+/// * `Clock::new()`: the process-wide `TSC::global()`
+/// * `now()` / `raw()`: `TSC::read_tsc()`. Unlike quanta's, `now()` returns the raw counter
+///   rather than an `Instant`
+/// * `scaled(raw)`: `TSC::tsc_to_ns()`
+/// * `delta(start, end)` / `delta_as_nanos(start, end)`: the time between two raw values,
+///   saturating to 0 if `end` is earlier (`TSC::delta_ns_saturating()`)
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    tsc: TSC,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::with_tsc(*TSC::global())
+    }
+
+    /// A clock converting with `tsc`'s frequency instead of the global one
+    pub fn with_tsc(tsc: TSC) -> Self {
+        Self { tsc }
+    }
+
+    #[inline(always)]
+    pub fn now(&self) -> u64 {
+        TSC::read_tsc()
+    }
+
+    #[inline(always)]
+    pub fn raw(&self) -> u64 {
+        TSC::read_tsc()
+    }
+
+    /// Converts a raw value to nanoseconds
+    #[inline]
+    pub fn scaled(&self, value: u64) -> u64 {
+        self.tsc.tsc_to_ns(value)
+    }
+
+    pub fn delta(&self, start: u64, end: u64) -> Duration {
+        Duration::from_nanos(self.delta_as_nanos(start, end))
+    }
+
+    pub fn delta_as_nanos(&self, start: u64, end: u64) -> u64 {
+        self.tsc.delta_ns_saturating(start, end)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_quanta_clock() {
+    let clock = Clock::with_tsc(TSC::from_freq(2_000_000_000));
+    assert_eq!(clock.scaled(3_000), 1_500);
+    assert_eq!(clock.delta(1_000, 5_000), Duration::from_nanos(2_000));
+    assert_eq!(clock.delta_as_nanos(5_000, 1_000), 0);
+
+    let start = clock.raw();
+    assert!(clock.now() >= start);
+}
@@ -0,0 +1,71 @@
+use crate::TSC;
+
+/// A running rate (e.g. bytes per second) of a monotonically growing count, from the count and
+/// TSC at the previous update, for cheap instantaneous throughput figures
+#[derive(Debug, Clone, Copy)]
+pub struct RateMeter {
+    last_tsc: u64,
+    last_count: u64,
+    rate: f64,
+}
+
+impl RateMeter {
+    /// Starts measuring from `count` at the current `read_tsc()`
+    pub fn new(count: u64) -> Self {
+        Self {
+            last_tsc: TSC::read_tsc(),
+            last_count: count,
+            rate: 0.0,
+        }
+    }
+
+    /// Units per second since the previous update, with the count now at `count`
+    #[inline]
+    pub fn update(&mut self, count: u64, tsc: &TSC) -> f64 {
+        self.update_at(count, TSC::read_tsc(), tsc)
+    }
+
+    /// Like `update()`, but with the TSC value `raw` read by the caller. If no cycles have
+    /// passed, or the counter went backwards (e.g. after migrating to another core), the
+    /// previous rate is returned, and in the latter case `raw` becomes the new starting point
+    pub fn update_at(&mut self, count: u64, raw: u64, tsc: &TSC) -> f64 {
+        let Some(cycles) = raw.checked_sub(self.last_tsc) else {
+            self.last_tsc = raw;
+            self.last_count = count;
+            return self.rate;
+        };
+        if cycles == 0 {
+            return self.rate;
+        }
+        let units = count.saturating_sub(self.last_count);
+        self.rate = units as f64 * tsc.freq_f64() / cycles as f64;
+        self.last_tsc = raw;
+        self.last_count = count;
+        self.rate
+    }
+
+    /// The rate computed by the last update, 0 before the first
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+}
+
+#[test]
+fn test_rate_meter() {
+    let t = TSC::from_freq(1_000_000_000);
+    let mut meter = RateMeter {
+        last_tsc: 1_000,
+        last_count: 0,
+        rate: 0.0,
+    };
+    assert_eq!(meter.update_at(500, 1_000 + 500_000_000, &t), 1_000.0);
+    assert_eq!(meter.update_at(600, 1_000 + 500_000_000, &t), 1_000.0);
+    assert_eq!(meter.update_at(2_500, 1_000 + 1_000_000_000, &t), 4_000.0);
+
+    assert_eq!(meter.update_at(3_000, 10, &t), 4_000.0);
+    assert_eq!(meter.update_at(3_100, 10 + 1_000_000_000, &t), 100.0);
+    assert_eq!(meter.rate(), 100.0);
+
+    let mut live = RateMeter::new(0);
+    assert!(live.update(1_000, &t) >= 0.0);
+}
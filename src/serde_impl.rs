@@ -0,0 +1,59 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+use crate::TSC;
+
+// only the rate is serialized; everything else is derived from it on deserialization. The exact
+// tick length is only written for clocks whose frequency isn't a whole number of Hz
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "TSC")]
+struct TscRepr {
+    freq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ns_per_tick: Option<(u64, u64)>,
+}
+
+impl Serialize for TSC {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ns_per_tick = self.ns_per_tick();
+        TscRepr {
+            freq: self.freq,
+            ns_per_tick: (ns_per_tick != TSC::from_freq(self.freq).ns_per_tick())
+                .then_some(ns_per_tick),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TSC {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = TscRepr::deserialize(deserializer)?;
+        if let Some((numer, denom)) = repr.ns_per_tick {
+            return TSC::from_ns_per_tick(numer, denom).map_err(D::Error::custom);
+        }
+        if repr.freq == 0 {
+            return Err(D::Error::custom("TSC frequency must be nonzero"));
+        }
+        Ok(TSC::from_freq(repr.freq).with_start_now())
+    }
+}
+
+#[test]
+fn test_serde_roundtrip() {
+    let t = TSC::from_freq(2_400_000_000);
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(json, r#"{"freq":2400000000}"#);
+    let t2: TSC = serde_json::from_str(&json).unwrap();
+    assert_eq!(t2.get_freq(), t.get_freq());
+
+    assert!(serde_json::from_str::<TSC>(r#"{"freq":0}"#).is_err());
+
+    let t = TSC::from_ns_per_tick(125, 3).unwrap();
+    assert_eq!(serde_json::to_string(&t).unwrap(), r#"{"freq":24000000}"#);
+    let t = TSC::from_ns_per_tick(1, 3).unwrap();
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(json, r#"{"freq":3000000000}"#);
+    let t = TSC::from_ns_per_tick(7, 3).unwrap();
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(json, r#"{"freq":428571429,"ns_per_tick":[7,3]}"#);
+    assert_eq!(serde_json::from_str::<TSC>(&json).unwrap(), t);
+}
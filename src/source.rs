@@ -0,0 +1,84 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::TSC;
+
+/// Something that produces raw TSC values. Code that takes a source instead of calling
+/// `TSC::read_tsc()` directly can be driven by a `ManualTsc` in tests
+pub trait TscSource {
+    fn read(&self) -> u64;
+}
+
+/// The hardware counter, through `TSC::read_tsc()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealTsc;
+
+impl TscSource for RealTsc {
+    #[inline(always)]
+    fn read(&self) -> u64 {
+        TSC::read_tsc()
+    }
+}
+
+/// A synthetic counter that only moves when told to, for advancing time deterministically
+#[derive(Debug, Default)]
+pub struct ManualTsc {
+    value: AtomicU64,
+}
+
+impl ManualTsc {
+    pub fn new(value: u64) -> Self {
+        Self {
+            value: AtomicU64::new(value),
+        }
+    }
+
+    pub fn set(&self, value: u64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    /// Moves the counter forward by `ticks`, wrapping like the hardware counter would
+    pub fn advance(&self, ticks: u64) {
+        self.value.fetch_add(ticks, Ordering::Relaxed);
+    }
+}
+
+impl TscSource for ManualTsc {
+    fn read(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: TscSource + ?Sized> TscSource for &S {
+    fn read(&self) -> u64 {
+        (**self).read()
+    }
+}
+
+impl TSC {
+    /// Like `now_ns()`, but reads the counter from `source`
+    pub fn now_ns_from(&self, source: &impl TscSource) -> u64 {
+        self.tsc_to_ns(source.read())
+    }
+
+    /// Like `elapsed_ns()`, but reads the counter from `source`
+    pub fn elapsed_ns_from(&self, source: &impl TscSource, start_tsc: u64) -> u64 {
+        self.delta_ns_saturating(start_tsc, source.read())
+    }
+}
+
+#[test]
+fn test_manual_tsc() {
+    let t = TSC::from_freq(1_000_000_000);
+    let clock = ManualTsc::new(5_000);
+    assert_eq!(t.now_ns_from(&clock), 5_000);
+
+    let start = clock.read();
+    clock.advance(1_500);
+    assert_eq!(t.elapsed_ns_from(&clock, start), 1_500);
+    clock.set(0);
+    assert_eq!(t.elapsed_ns_from(&clock, start), 0);
+
+    let real = RealTsc;
+    let n0 = t.now_ns_from(&real);
+    assert!(t.now_ns_from(&real) >= n0);
+}
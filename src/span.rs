@@ -0,0 +1,69 @@
+use crate::TSC;
+
+/// Receives a span's label and elapsed nanoseconds when it's dropped
+pub type SpanSink<'a> = &'a dyn Fn(&str, u64);
+
+/// An RAII timer created by `TSC::span()`: captures the TSC when created and, when dropped,
+/// passes its label and the elapsed nanoseconds to its sink (if one was set with `with_sink()`)
+pub struct Span<'a> {
+    tsc: &'a TSC,
+    label: &'a str,
+    start: u64,
+    sink: Option<SpanSink<'a>>,
+}
+
+impl TSC {
+    pub fn span<'a>(&'a self, label: &'a str) -> Span<'a> {
+        Span {
+            tsc: self,
+            label,
+            start: self.read(),
+            sink: None,
+        }
+    }
+}
+
+impl<'a> Span<'a> {
+    pub fn with_sink(self, sink: SpanSink<'a>) -> Self {
+        Self {
+            sink: Some(sink),
+            ..self
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.label
+    }
+
+    pub fn elapsed_ns(&self) -> u64 {
+        self.tsc.elapsed_ns(self.start)
+    }
+}
+
+impl Drop for Span<'_> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.sink {
+            sink(self.label, self.elapsed_ns());
+        }
+    }
+}
+
+#[test]
+fn test_span() {
+    use std::cell::RefCell;
+
+    let t = TSC::from_freq(1_000_000_000);
+    let recorded = RefCell::new(vec![]);
+    let sink = |label: &str, ns| recorded.borrow_mut().push((label.to_owned(), ns));
+
+    {
+        let _outer = t.span("outer").with_sink(&sink);
+        let _inner = t.span("inner").with_sink(&sink);
+        let _silent = t.span("silent");
+    }
+    let recorded = recorded.into_inner();
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(recorded[0].0, "inner");
+    assert_eq!(recorded[1].0, "outer");
+    assert!(recorded[1].1 >= recorded[0].1);
+}
@@ -0,0 +1,277 @@
+use std::sync::atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+use crate::{Error, TSC};
+
+impl TSC {
+    /// Estimates the offset (in cycles) between the TSCs of two cores, by ping-ponging
+    /// `read_tscp()` timestamps between the calling thread and a spawned one for `samples` rounds.
+    /// Each round brackets the other thread's read between two local reads; the round with the
+    /// tightest bracket gives the returned estimate of `other - local`. A value close to zero
+    /// (relative to the read overhead) means the counters are synchronized; a large one means
+    /// threads should be pinned before trusting deltas. On Linux the calling thread is pinned to
+    /// its current core and the other thread to a different one, so the threads can't migrate
+    /// mid-measurement; elsewhere the scheduler decides. Either way the result only covers the
+    /// pair of cores actually used. Fails with `Error::NoSamples` if `samples` is 0
+    pub fn check_sync(samples: usize) -> Result<i64, Error> {
+        if samples == 0 {
+            return Err(Error::NoSamples);
+        }
+        if !Self::has_rdtscp() {
+            return Err(Error::RdtscpNotSupported);
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(local) = current_core()
+            && let Some(other) = allowed_cores().into_iter().find(|&core| core != local)
+            && let Some(offset) =
+                Self::with_pinned_core(local, || Self::ping_pong(samples, || pin_to_core(other)))
+        {
+            return Ok(offset);
+        }
+        let offset = Self::ping_pong(samples, || true);
+        Ok(offset.expect("setup can't fail"))
+    }
+
+    /// Runs `f` with the calling thread pinned to `core`, then restores the thread's previous
+    /// affinity (also if `f` panics). Timing code that stays on one core can't see the jump
+    /// between two cores' counters, nor lose time to a migration. If the thread can't be pinned
+    /// (e.g. `core` is outside the allowed set), `f` runs unpinned
+    #[cfg(target_os = "linux")]
+    pub fn with_pinned_core<R>(core: usize, f: impl FnOnce() -> R) -> R {
+        struct Restore(libc::cpu_set_t);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &self.0) };
+            }
+        }
+
+        let _restore = affinity().filter(|_| pin_to_core(core)).map(Restore);
+        f()
+    }
+
+    /// Runs `f`; there's no affinity control on this platform to pin the thread with
+    #[cfg(not(target_os = "linux"))]
+    pub fn with_pinned_core<R>(_core: usize, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
+    // `with_pinned_core()` on whichever core the thread is on now
+    pub(crate) fn on_current_core<R>(f: impl FnOnce() -> R) -> R {
+        #[cfg(target_os = "linux")]
+        if let Some(core) = current_core() {
+            return Self::with_pinned_core(core, f);
+        }
+        f()
+    }
+
+    // the `other - local` offset estimate behind `check_sync()`, with `setup` run on the spawned
+    // thread before the first round; `None` if `setup` fails
+    fn ping_pong(samples: usize, setup: impl FnOnce() -> bool + Send) -> Option<i64> {
+        const PENDING: u8 = 0;
+        const READY: u8 = 1;
+        const FAILED: u8 = 2;
+
+        let ready = AtomicU8::new(PENDING);
+        let seq = AtomicUsize::new(0);
+        let stamp = AtomicU64::new(0);
+        let wait_for = |expected: usize| {
+            while seq.load(Ordering::Acquire) != expected {
+                core::hint::spin_loop();
+                std::thread::yield_now();
+            }
+        };
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                if !setup() {
+                    ready.store(FAILED, Ordering::Release);
+                    return;
+                }
+                ready.store(READY, Ordering::Release);
+                for i in 0..samples {
+                    wait_for(2 * i + 1);
+                    stamp.store(Self::read_tscp().0, Ordering::Relaxed);
+                    seq.store(2 * i + 2, Ordering::Release);
+                }
+            });
+
+            loop {
+                match ready.load(Ordering::Acquire) {
+                    PENDING => std::thread::yield_now(),
+                    READY => break,
+                    _ => return None,
+                }
+            }
+            let mut best = (u64::MAX, 0);
+            for i in 0..samples {
+                let before = Self::read_tscp().0;
+                seq.store(2 * i + 1, Ordering::Release);
+                wait_for(2 * i + 2);
+                let after = Self::read_tscp().0;
+
+                let rtt = after.wrapping_sub(before);
+                if rtt < best.0 {
+                    let midpoint = before as i128 + (rtt / 2) as i128;
+                    best = (
+                        rtt,
+                        (stamp.load(Ordering::Relaxed) as i128 - midpoint) as i64,
+                    );
+                }
+            }
+            Some(best.1)
+        })
+    }
+}
+
+/// The TSC offset of each core relative to a reference core, for timestamps that agree across
+/// cores even when the counters aren't perfectly synchronized. Measuring pins a pair of threads
+/// to the reference core and each other core in turn for `samples` rounds of the `check_sync()`
+/// ping-pong, so it costs two thread spawns and `samples` cross-core round trips per core: tens
+/// of milliseconds on large machines. Offsets can drift (e.g. across suspend or when firmware
+/// writes `IA32_TSC_ADJUST`), so long-running processes should remeasure periodically
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub struct TscOffsets {
+    tsc: TSC,
+    reference: usize,
+    // `other - reference` in cycles, indexed by core id; `None` for cores that weren't measured
+    offsets: Vec<Option<i64>>,
+}
+
+#[cfg(target_os = "linux")]
+impl TscOffsets {
+    /// Measures every core the process may run on, relative to the first of them. Cores that
+    /// can't be pinned are left unmeasured. Fails with `Error::NoSamples` if `samples` is 0
+    pub fn measure(tsc: TSC, samples: usize) -> Result<Self, Error> {
+        if samples == 0 {
+            return Err(Error::NoSamples);
+        }
+        if !TSC::has_rdtscp() {
+            return Err(Error::RdtscpNotSupported);
+        }
+        let cores = allowed_cores();
+        let reference = cores.first().copied().unwrap_or(0);
+        let mut offsets = vec![None; cores.last().map_or(0, |&last| last + 1)];
+
+        for &core in &cores {
+            offsets[core] = if core == reference {
+                Some(0)
+            } else {
+                std::thread::spawn(move || {
+                    if !pin_to_core(reference) {
+                        return None;
+                    }
+                    TSC::ping_pong(samples, || pin_to_core(core))
+                })
+                .join()
+                .unwrap()
+            };
+        }
+        Ok(Self {
+            tsc,
+            reference,
+            offsets,
+        })
+    }
+
+    pub fn reference_core(&self) -> usize {
+        self.reference
+    }
+
+    /// The offset of `core` relative to the reference core, in cycles
+    pub fn offset(&self, core: usize) -> Option<i64> {
+        self.offsets.get(core).copied().flatten()
+    }
+
+    /// Like `TSC::now_ns()`, but with the current core's offset subtracted, so the result is on
+    /// the reference core's timeline. Reads on unmeasured cores are left uncorrected
+    pub fn now_ns_compensated(&self) -> u64 {
+        let (raw, aux) = TSC::read_tscp();
+        // Linux keeps the core id in the low 12 bits of `IA32_TSC_AUX`
+        let offset = self.offset((aux & 0xfff) as usize).unwrap_or(0);
+        self.tsc.tsc_to_ns(raw.wrapping_sub(offset as u64))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn affinity() -> Option<libc::cpu_set_t> {
+    let mut set: libc::cpu_set_t = unsafe { core::mem::zeroed() };
+    let res = unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) };
+    (res == 0).then_some(set)
+}
+
+// the cores in the calling thread's affinity mask
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> Vec<usize> {
+    let Some(set) = affinity() else {
+        return Vec::new();
+    };
+    (0..libc::CPU_SETSIZE as usize)
+        .filter(|&core| unsafe { libc::CPU_ISSET(core, &set) })
+        .collect()
+}
+
+// the core the calling thread is running on right now
+#[cfg(target_os = "linux")]
+fn current_core() -> Option<usize> {
+    usize::try_from(unsafe { libc::sched_getcpu() }).ok()
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) -> bool {
+    if core >= libc::CPU_SETSIZE as usize {
+        return false;
+    }
+    let mut set: libc::cpu_set_t = unsafe { core::mem::zeroed() };
+    unsafe {
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[test]
+fn test_with_pinned_core() {
+    #[cfg(target_os = "linux")]
+    {
+        let before = allowed_cores();
+        let core = *before.last().unwrap();
+        let (pinned, on) = TSC::with_pinned_core(core, || (allowed_cores(), current_core()));
+        assert_eq!(pinned, [core]);
+        assert_eq!(on, Some(core));
+        assert_eq!(allowed_cores(), before);
+
+        let res = std::panic::catch_unwind(|| TSC::with_pinned_core(core, || panic!()));
+        assert!(res.is_err());
+        assert_eq!(allowed_cores(), before);
+    }
+    assert_eq!(TSC::with_pinned_core(usize::MAX / 2, || 42), 42);
+}
+
+#[test]
+fn test_check_sync() {
+    assert_eq!(TSC::check_sync(0), Err(Error::NoSamples));
+    if !TSC::has_rdtscp() {
+        assert_eq!(TSC::check_sync(10), Err(Error::RdtscpNotSupported));
+        return;
+    }
+    let offset = TSC::check_sync(100).unwrap();
+    println!("offset={offset}");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_tsc_offsets() {
+    let t = TSC::from_freq(3_000_000_000);
+    assert_eq!(TscOffsets::measure(t, 0).unwrap_err(), Error::NoSamples);
+    if !TSC::has_rdtscp() {
+        assert_eq!(
+            TscOffsets::measure(t, 10).unwrap_err(),
+            Error::RdtscpNotSupported
+        );
+        return;
+    }
+    let offsets = TscOffsets::measure(t, 100).unwrap();
+    assert_eq!(offsets.offset(offsets.reference_core()), Some(0));
+    println!("{offsets:?}");
+    let n0 = offsets.now_ns_compensated();
+    assert!(n0 > 0);
+}
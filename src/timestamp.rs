@@ -0,0 +1,48 @@
+use core::ops::Sub;
+
+use crate::TSC;
+
+/// A raw TSC reading, kept distinct from other `u64`s. Subtracting two readings gives a
+/// `TscDelta`, which a `TSC` converts to time
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tsc(pub u64);
+
+/// The number of TSC ticks between two `Tsc` readings
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TscDelta(pub u64);
+
+impl Tsc {
+    #[inline(always)]
+    pub fn now() -> Self {
+        Self(TSC::read_tsc())
+    }
+}
+
+// like `Instant`, a later reading subtracted from an earlier one saturates to zero
+impl Sub for Tsc {
+    type Output = TscDelta;
+
+    fn sub(self, rhs: Self) -> TscDelta {
+        TscDelta(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl TscDelta {
+    pub fn to_ns(self, tsc: &TSC) -> u64 {
+        tsc.tsc_to_ns(self.0)
+    }
+}
+
+#[test]
+fn test_tsc_delta() {
+    let t = TSC::from_freq(2_000_000_000);
+    assert_eq!((Tsc(5_000) - Tsc(1_000)).to_ns(&t), 2_000);
+    assert_eq!(Tsc(1_000) - Tsc(5_000), TscDelta(0));
+
+    let t0 = Tsc::now();
+    assert!(Tsc::now() >= t0);
+}
@@ -0,0 +1,78 @@
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::TSC;
+
+/// A flight recorder of the last `N` raw TSC readings, e.g. to dump the timing of the final
+/// events before a crash. Recording is wait-free (an index increment and a store) and never
+/// allocates, so it can be left on in hot paths. Readings recorded concurrently with a
+/// `snapshot()` may show up torn (an older reading in a slot just claimed by a newer one)
+#[derive(Debug)]
+pub struct TscTrace<const N: usize> {
+    slots: [AtomicU64; N],
+    // the number of readings ever recorded; the next one goes to slot `next % N`
+    next: AtomicUsize,
+}
+
+impl<const N: usize> TscTrace<N> {
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "TscTrace needs at least one slot") };
+        Self {
+            slots: [const { AtomicU64::new(0) }; N],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Stores the current `read_tsc()`, overwriting the oldest reading once the ring is full
+    #[inline]
+    pub fn record(&self) {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        self.slots[index % N].store(TSC::read_tsc(), Ordering::Relaxed);
+    }
+
+    /// The number of readings recorded so far, including those already overwritten
+    pub fn recorded(&self) -> usize {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// The retained readings (at most `N`) converted to nanoseconds, oldest first
+    #[cfg(feature = "std")]
+    pub fn snapshot(&self, tsc: &TSC) -> Vec<u64> {
+        let recorded = self.recorded();
+        let (first, len) = if recorded <= N {
+            (0, recorded)
+        } else {
+            (recorded % N, N)
+        };
+        (0..len)
+            .map(|i| tsc.tsc_to_ns(self.slots[(first + i) % N].load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+impl<const N: usize> Default for TscTrace<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_trace() {
+    let t = TSC::from_freq(1_000_000_000);
+    let trace = TscTrace::<4>::new();
+    assert!(trace.snapshot(&t).is_empty());
+
+    trace.record();
+    trace.record();
+    let partial = trace.snapshot(&t);
+    assert_eq!(partial.len(), 2);
+    assert!(partial[0] <= partial[1]);
+
+    for _ in 0..10 {
+        trace.record();
+    }
+    assert_eq!(trace.recorded(), 12);
+    let full = trace.snapshot(&t);
+    assert_eq!(full.len(), 4);
+    assert!(full.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(full[0] >= partial[1]);
+}
@@ -0,0 +1,38 @@
+use tracing::Span;
+
+use crate::TSC;
+
+impl TSC {
+    /// Runs `f` inside `span` and records how long it took, in nanoseconds, as the span's
+    /// `elapsed_ns` field. The field must be declared when the span is created (e.g.
+    /// `elapsed_ns = tracing::field::Empty`), which `tsc_span!` does
+    pub fn in_span<R>(&self, span: &Span, f: impl FnOnce() -> R) -> R {
+        let start = self.read();
+        let res = span.in_scope(f);
+        span.record("elapsed_ns", self.elapsed_ns(start));
+        res
+    }
+}
+
+/// Evaluates `$body` inside a new `tracing` span, timed by `$tsc` (see `TSC::in_span()`):
+///
+/// ```ignore
+/// let sum = tsc::tsc_span!(tsc, tracing::Level::INFO, "sum", { (0..100u64).sum::<u64>() });
+/// ```
+#[macro_export]
+macro_rules! tsc_span {
+    ($tsc:expr, $level:expr, $name:expr, $body:expr) => {{
+        let span =
+            $crate::__tracing::span!($level, $name, elapsed_ns = $crate::__tracing::field::Empty);
+        $tsc.in_span(&span, || $body)
+    }};
+}
+
+#[test]
+fn test_tsc_span() {
+    let tsc = TSC::from_freq(2_000_000_000);
+    let sum = tsc_span!(tsc, tracing::Level::INFO, "sum", {
+        (0..100u64).sum::<u64>()
+    });
+    assert_eq!(sum, 4950);
+}